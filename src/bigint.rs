@@ -0,0 +1,481 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// An arbitrary-precision integer in sign-magnitude form. The magnitude is
+/// stored little-endian in base 2^32 without leading zero limbs, so every
+/// value has exactly one representation and zero is never negative.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BigInt {
+    negative: bool,
+    mag: Vec<u32>,
+}
+
+impl From<i64> for BigInt {
+    fn from(i: i64) -> Self {
+        let m = i.unsigned_abs();
+        BigInt::from_mag(i < 0, vec![m as u32, (m >> 32) as u32])
+    }
+}
+
+impl BigInt {
+    fn from_mag(negative: bool, mut mag: Vec<u32>) -> Self {
+        trim(&mut mag);
+        BigInt { negative: negative && !mag.is_empty(), mag }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mag.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.mag.first().is_none_or(|d| d % 2 == 0)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.mag.len() > 2 {
+            return None;
+        }
+        let m = self.mag.iter().rev().fold(0u64, |acc, &d| (acc << 32) | u64::from(d));
+        if self.negative {
+            if m <= 1 << 63 { Some((m as i64).wrapping_neg()) } else { None }
+        } else if m <= i64::MAX as u64 {
+            Some(m as i64)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let m = self.mag.iter().rev().fold(0.0, |acc, &d| acc * 4294967296.0 + f64::from(d));
+        if self.negative { -m } else { m }
+    }
+
+    pub fn neg(&self) -> Self {
+        BigInt::from_mag(!self.negative, self.mag.clone())
+    }
+
+    pub fn abs(&self) -> Self {
+        BigInt::from_mag(false, self.mag.clone())
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return BigInt::from_mag(self.negative, add_mag(&self.mag, &other.mag));
+        }
+        match cmp_mag(&self.mag, &other.mag) {
+            Ordering::Less => BigInt::from_mag(other.negative, sub_mag(&other.mag, &self.mag)),
+            _ => BigInt::from_mag(self.negative, sub_mag(&self.mag, &other.mag)),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        BigInt::from_mag(self.negative != other.negative, mul_mag(&self.mag, &other.mag))
+    }
+
+    /// Truncating division, as in `quotient` and `remainder`: the remainder
+    /// has the sign of `self`. Returns `None` when `other` is zero.
+    pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = div_rem_mag(&self.mag, &other.mag);
+        Some((BigInt::from_mag(self.negative != other.negative, q), BigInt::from_mag(self.negative, r)))
+    }
+
+    /// The greatest common divisor, which is never negative.
+    pub fn gcd(&self, other: &Self) -> Self {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let r = a.div_rem(&b).unwrap().1;
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    /// The number of bits in the magnitude.
+    pub fn bits(&self) -> u64 {
+        match self.mag.last() {
+            Some(top) => self.mag.len() as u64 * 32 - u64::from(top.leading_zeros()),
+            None => 0,
+        }
+    }
+
+    /// The integer square root of the magnitude, rounded down.
+    pub fn sqrt(&self) -> Self {
+        if self.is_zero() {
+            return self.clone();
+        }
+        let n = self.abs();
+        let two = BigInt::from(2);
+        // Newton's method from a starting point above the root
+        let mut x = two.pow(self.bits().div_ceil(2) as u32);
+        loop {
+            let y = x.add(&n.div_rem(&x).unwrap().0).div_rem(&two).unwrap().0;
+            if y >= x {
+                return x;
+            }
+            x = y;
+        }
+    }
+
+    pub fn pow(&self, mut exp: u32) -> Self {
+        let mut base = self.clone();
+        let mut acc = BigInt::from(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = acc.mul(&base);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul(&base);
+            }
+        }
+        acc
+    }
+
+    /// Parses an optionally signed string of digits in `radix` (2 to 36).
+    pub fn parse(s: &str, radix: u32) -> Option<Self> {
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        let mut mag = Vec::new();
+        for ch in digits.chars() {
+            mul_small(&mut mag, radix, ch.to_digit(radix)?);
+        }
+        Some(BigInt::from_mag(negative, mag))
+    }
+
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        if self.is_zero() {
+            return "0".to_string();
+        }
+        // peel off as many digits at a time as fit in a limb
+        let mut width = 1;
+        let mut chunk = radix;
+        while let Some(next) = chunk.checked_mul(radix) {
+            chunk = next;
+            width += 1;
+        }
+        let mut chunks = Vec::new();
+        let mut mag = self.mag.clone();
+        while !mag.is_empty() {
+            let (q, r) = div_rem_small(&mag, chunk);
+            chunks.push(r);
+            mag = q;
+        }
+        let mut s = String::new();
+        if self.negative {
+            s.push('-');
+        }
+        for (i, &c) in chunks.iter().rev().enumerate() {
+            let digits = digits_in_radix(c, radix);
+            if i > 0 {
+                s.extend(std::iter::repeat_n('0', width - digits.len()));
+            }
+            s.push_str(&digits);
+        }
+        s
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.mag, &other.mag),
+            (true, true) => cmp_mag(&other.mag, &self.mag),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_radix(10))
+    }
+}
+
+fn digits_in_radix(mut n: u32, radix: u32) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(std::char::from_digit(n % radix, radix).unwrap());
+        n /= radix;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+fn trim(mag: &mut Vec<u32>) {
+    while let Some(&0) = mag.last() {
+        mag.pop();
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut res = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &d) in long.iter().enumerate() {
+        let sum = u64::from(d) + u64::from(short.get(i).copied().unwrap_or(0)) + carry;
+        res.push(sum as u32);
+        carry = sum >> 32;
+    }
+    res.push(carry as u32);
+    res
+}
+
+// a - b where |a| >= |b|
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &d) in a.iter().enumerate() {
+        let diff = i64::from(d) - i64::from(b.get(i).copied().unwrap_or(0)) - borrow;
+        res.push(diff as u32);
+        borrow = if diff < 0 { 1 } else { 0 };
+    }
+    res
+}
+
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut res = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let t = u64::from(res[i + j]) + u64::from(x) * u64::from(y) + carry;
+            res[i + j] = t as u32;
+            carry = t >> 32;
+        }
+        res[i + b.len()] = carry as u32;
+    }
+    res
+}
+
+// mag = mag * m + add
+fn mul_small(mag: &mut Vec<u32>, m: u32, add: u32) {
+    let mut carry = u64::from(add);
+    for d in mag.iter_mut() {
+        let t = u64::from(*d) * u64::from(m) + carry;
+        *d = t as u32;
+        carry = t >> 32;
+    }
+    if carry > 0 {
+        mag.push(carry as u32);
+    }
+}
+
+fn div_rem_small(a: &[u32], d: u32) -> (Vec<u32>, u32) {
+    let mut q = vec![0u32; a.len()];
+    let mut rem = 0u64;
+    for i in (0..a.len()).rev() {
+        let cur = (rem << 32) | u64::from(a[i]);
+        q[i] = (cur / u64::from(d)) as u32;
+        rem = cur % u64::from(d);
+    }
+    trim(&mut q);
+    (q, rem as u32)
+}
+
+// Long division of magnitudes, Knuth's algorithm D.
+fn div_rem_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_mag(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let (q, r) = div_rem_small(a, b[0]);
+        return (q, vec![r]);
+    }
+    // normalize so the top limb of the divisor has its high bit set
+    let shift = b[b.len() - 1].leading_zeros();
+    let mut b = shl(b, shift);
+    b.pop();
+    let mut a = shl(a, shift);
+    let n = b.len();
+    let m = a.len() - n;
+    let top = u64::from(b[n - 1]);
+    let next = u64::from(b[n - 2]);
+    let mut q = vec![0u32; m];
+    for j in (0..m).rev() {
+        let num = (u64::from(a[j + n]) << 32) | u64::from(a[j + n - 1]);
+        let mut qhat = num / top;
+        let mut rhat = num % top;
+        while qhat >= 1 << 32 || qhat * next > ((rhat << 32) | u64::from(a[j + n - 2])) {
+            qhat -= 1;
+            rhat += top;
+            if rhat >= 1 << 32 {
+                break;
+            }
+        }
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let p = qhat * u64::from(b[i]) + carry;
+            carry = p >> 32;
+            let t = i64::from(a[i + j]) - borrow - (p & 0xffff_ffff) as i64;
+            a[i + j] = t as u32;
+            borrow = if t < 0 { 1 } else { 0 };
+        }
+        let t = i64::from(a[j + n]) - borrow - carry as i64;
+        a[j + n] = t as u32;
+        if t < 0 {
+            // qhat was one too large; add the divisor back
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u64::from(a[i + j]) + u64::from(b[i]) + carry;
+                a[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            a[j + n] = a[j + n].wrapping_add(carry as u32);
+        }
+        q[j] = qhat as u32;
+    }
+    let mut r = shr(&a[..n], shift);
+    trim(&mut q);
+    trim(&mut r);
+    (q, r)
+}
+
+// Shifts left by `shift` < 32 bits, always adding a limb for the bits
+// shifted out.
+fn shl(a: &[u32], shift: u32) -> Vec<u32> {
+    let mut res = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for &d in a {
+        if shift == 0 {
+            res.push(d);
+        } else {
+            res.push((d << shift) | carry);
+            carry = d >> (32 - shift);
+        }
+    }
+    res.push(carry);
+    res
+}
+
+fn shr(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    (0..a.len()).map(|i| {
+        let high = a.get(i + 1).map_or(0, |&d| d << (32 - shift));
+        (a[i] >> shift) | high
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s, 10).unwrap()
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(BigInt::from(i64::MAX).to_i64(), Some(i64::MAX));
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("-9223372036854775808").to_i64(), Some(i64::MIN));
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(big("+00123").to_string(), "123");
+        assert_eq!(big("18446744073709551616").to_f64(), 18446744073709551616.0);
+        assert_eq!(BigInt::parse("ff", 16), Some(BigInt::from(255)));
+        assert_eq!(BigInt::parse("12a", 10), None);
+        assert_eq!(BigInt::parse("-", 10), None);
+        assert_eq!(big("-255").to_string_radix(16), "-ff");
+        assert_eq!(big("1000000000000000000000").to_string_radix(2).len(), 70);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+        assert_eq!(a.add(&b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(a.sub(&b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(a.mul(&b).to_string(), "-121932631137021795226185032733622923332237463801111263526900");
+        assert_eq!(a.sub(&a), big("0"));
+        assert_eq!(big("2").pow(100).to_string(), "1267650600228229401496703205376");
+        assert_eq!(big("-12").gcd(&big("18")).to_string(), "6");
+        assert_eq!(big("0").gcd(&big("-7")).to_string(), "7");
+        assert_eq!(big("99999999999999999999").sqrt().to_string(), "9999999999");
+        assert_eq!(big("100000000000000000000").sqrt().to_string(), "10000000000");
+        assert_eq!(big("15").sqrt().to_string(), "3");
+        assert_eq!(big("1").sqrt().to_string(), "1");
+        assert_eq!(big("4294967296").bits(), 33);
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let a = big("121932631137021795226185032733622923332237463801111263526901");
+        let b = big("987654321098765432109876543210");
+        let (q, r) = a.div_rem(&b).unwrap();
+        assert_eq!(q.to_string(), "123456789012345678901234567890");
+        assert_eq!(r.to_string(), "1");
+        let (q, r) = a.neg().div_rem(&b).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), ("-123456789012345678901234567890".to_string(), "-1".to_string()));
+        let (q, r) = big("18446744073709551615").div_rem(&big("4294967296")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), ("4294967295".to_string(), "4294967295".to_string()));
+        assert_eq!(a.div_rem(&big("0")), None);
+        // a case where the first estimate of a quotient digit is too large
+        let (q, r) = big("340282366920938463463374607431768211455").div_rem(&big("18446744073709551617")).unwrap();
+        assert_eq!((q.to_string(), r.to_string()), ("18446744073709551615".to_string(), "0".to_string()));
+    }
+
+    #[test]
+    fn test_div_rem_identity() {
+        // a = q * b + r with |r| < |b| on pseudo-random operands
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = |limbs: usize| {
+            let mag = (0..limbs).map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                (seed >> 16) as u32
+            }).collect();
+            BigInt::from_mag(seed.is_multiple_of(3), mag)
+        };
+        for i in 0..200 {
+            let a = next(1 + i % 7);
+            let b = next(1 + i % 4);
+            if b.is_zero() {
+                continue;
+            }
+            let (q, r) = a.div_rem(&b).unwrap();
+            assert_eq!(q.mul(&b).add(&r), a);
+            assert!(cmp_mag(&r.mag, &b.mag) == Ordering::Less);
+        }
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(big("-5") < big("3"));
+        assert!(big("-5") < big("-3"));
+        assert!(big("100000000000000000000") > big("99999999999999999999"));
+    }
+}
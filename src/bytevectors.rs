@@ -0,0 +1,195 @@
+use std::convert::TryFrom;
+
+use crate::interpreter::Interpreter;
+use crate::native::{Arity, position, bound, range};
+use crate::sexpr::{SExpr, Object};
+use crate::symbol::Symbol;
+
+// Bytevectors are `Object::Bytevector` cells holding raw bytes. Multi-byte
+// accessors take their endianness as the symbol `big` or `little`.
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("bytevector?", 1, |interp, args| {
+        Ok(interp.memory().bytevector_ref(args[0]).is_ok())
+    });
+    interpreter.define_builtin("make-bytevector", Arity::Between(1, 2), |interp, args| {
+        let k = bound(interp, args[0], usize::MAX)?;
+        let fill = match args.get(1) {
+            Some(&b) => byte(interp, b)?,
+            None => 0,
+        };
+        let mut bytes = Vec::new();
+        bytes.try_reserve_exact(k).or(Err("bytevector too large"))?;
+        bytes.resize(k, fill);
+        Ok(alloc(interp, bytes))
+    });
+    interpreter.define_builtin("bytevector", Arity::AtLeast(0), |interp, args| {
+        let bytes = args.iter().map(|&b| byte(interp, b)).collect::<Result<_, _>>()?;
+        Ok(alloc(interp, bytes))
+    });
+    interpreter.define_builtin("bytevector-length", 1, |interp, args| {
+        Ok(bytevector(interp, args[0])?.len() as i64)
+    });
+    interpreter.define_builtin("bytevector-u8-ref", 2, |interp, args| {
+        let bytes = bytevector(interp, args[0])?;
+        let k = position(interp, args[1], bytes.len())?;
+        Ok(i64::from(bytes[k]))
+    });
+    interpreter.define_builtin("bytevector-u8-set!", 3, |interp, args| {
+        let k = position(interp, args[1], bytevector(interp, args[0])?.len())?;
+        let b = byte(interp, args[2])?;
+        bytevector_mut(interp, args[0])?[k] = b;
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("bytevector-copy", Arity::Between(1, 3), |interp, args| {
+        let bytes = bytevector(interp, args[0])?;
+        let (start, end) = range(interp, args, 1, bytes.len())?;
+        let bytes = bytes[start..end].to_vec();
+        Ok(alloc(interp, bytes))
+    });
+    interpreter.define_builtin("bytevector-copy!", Arity::Between(3, 5), |interp, args| {
+        let to_len = bytevector(interp, args[0])?.len();
+        let at = bound(interp, args[1], to_len)?;
+        let from = bytevector(interp, args[2])?;
+        let (start, end) = range(interp, args, 3, from.len())?;
+        if at + (end - start) > to_len {
+            return Err("index out of range");
+        }
+        let bytes = from[start..end].to_vec();
+        bytevector_mut(interp, args[0])?[at..at + bytes.len()].copy_from_slice(&bytes);
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("bytevector-append", Arity::AtLeast(0), |interp, args| {
+        let mut res = Vec::new();
+        for &bytes in args {
+            res.extend_from_slice(bytevector(interp, bytes)?);
+        }
+        Ok(alloc(interp, res))
+    });
+    interpreter.define_builtin("utf8->string", Arity::Between(1, 3), |interp, args| {
+        let bytes = bytevector(interp, args[0])?;
+        let (start, end) = range(interp, args, 1, bytes.len())?;
+        String::from_utf8(bytes[start..end].to_vec()).or(Err("invalid UTF-8"))
+    });
+    interpreter.define_builtin("string->utf8", Arity::Between(1, 3), |interp, args| {
+        let s = interp.memory().str_ref(args[0]).or(Err("type error: expected string"))?;
+        let (start, end) = range(interp, args, 1, s.len())?;
+        let bytes = s[start..end].iter().collect::<String>().into_bytes();
+        Ok(alloc(interp, bytes))
+    });
+
+    macro_rules! int_accessors {
+        ( $( $ty:ty, $ref_name:expr, $set_name:expr; )* ) => { $(
+            interpreter.define_builtin($ref_name, 3, |interp, args| {
+                const SIZE: usize = std::mem::size_of::<$ty>();
+                let big = big_endian(interp, args[2])?;
+                let at = offset(interp, args[0], args[1], SIZE)?;
+                let mut buf = [0u8; SIZE];
+                buf.copy_from_slice(&bytevector(interp, args[0])?[at..at + SIZE]);
+                let n = if big { <$ty>::from_be_bytes(buf) } else { <$ty>::from_le_bytes(buf) };
+                Ok(n as i64)
+            });
+            interpreter.define_builtin($set_name, 4, |interp, args| {
+                const SIZE: usize = std::mem::size_of::<$ty>();
+                let n: i64 = interp.from_sexpr(args[2])?;
+                let n = <$ty>::try_from(n).or(Err("value out of range"))?;
+                let big = big_endian(interp, args[3])?;
+                let at = offset(interp, args[0], args[1], SIZE)?;
+                let buf = if big { n.to_be_bytes() } else { n.to_le_bytes() };
+                bytevector_mut(interp, args[0])?[at..at + SIZE].copy_from_slice(&buf);
+                Ok(SExpr::Nil)
+            });
+        )* };
+    }
+
+    int_accessors! {
+        u16, "bytevector-u16-ref", "bytevector-u16-set!";
+        i16, "bytevector-s16-ref", "bytevector-s16-set!";
+        u32, "bytevector-u32-ref", "bytevector-u32-set!";
+        i32, "bytevector-s32-ref", "bytevector-s32-set!";
+        i64, "bytevector-s64-ref", "bytevector-s64-set!";
+    }
+}
+
+fn alloc(interp: &mut Interpreter, bytes: Vec<u8>) -> SExpr {
+    interp.memory_mut().alloc(Object::Bytevector(bytes))
+}
+
+fn bytevector(interp: &Interpreter, e: SExpr) -> Result<&Vec<u8>, &'static str> {
+    interp.memory().bytevector_ref(e).or(Err("type error: expected bytevector"))
+}
+
+fn bytevector_mut(interp: &mut Interpreter, e: SExpr) -> Result<&mut Vec<u8>, &'static str> {
+    interp.memory_mut().bytevector_mut(e).or(Err("type error: expected bytevector"))
+}
+
+fn byte(interp: &Interpreter, e: SExpr) -> Result<u8, &'static str> {
+    let b: i64 = interp.from_sexpr(e)?;
+    u8::try_from(b).or(Err("value out of range"))
+}
+
+// The index of the first of `size` bytes starting at `k`.
+fn offset(interp: &Interpreter, bytes: SExpr, k: SExpr, size: usize) -> Result<usize, &'static str> {
+    let len = bytevector(interp, bytes)?.len();
+    let at = bound(interp, k, len)?;
+    if at + size > len {
+        return Err("index out of range");
+    }
+    Ok(at)
+}
+
+fn big_endian(interp: &Interpreter, e: SExpr) -> Result<bool, &'static str> {
+    let sym: Symbol = interp.from_sexpr(e)?;
+    match interp.symbol_name(sym) {
+        "big" => Ok(true),
+        "little" => Ok(false),
+        _ => Err("endianness must be big or little"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!(eval("#u8(1 2 255)"), "#u8(1 2 255)");
+        assert_eq!(eval("(make-bytevector 3 7)"), "#u8(7 7 7)");
+        assert_eq!(eval("(bytevector 1 256)"), "value out of range");
+        assert_eq!(eval("(bytevector-length #u8(1 2))"), "2");
+        assert_eq!(eval("(bytevector-u8-ref #u8(1 2) 1)"), "2");
+        assert_eq!(eval("(bytevector-u8-ref #u8(1 2) 2)"), "index out of range");
+        assert_eq!(eval("(define b (make-bytevector 2)) (bytevector-u8-set! b 0 9) b"), "#u8(9 0)");
+        assert_eq!(eval("(bytevector? #u8())"), "true");
+        assert_eq!(eval("(bytevector? #(1))"), "false");
+    }
+
+    #[test]
+    fn test_copy_append() {
+        assert_eq!(eval("(bytevector-copy #u8(1 2 3 4) 1 3)"), "#u8(2 3)");
+        assert_eq!(eval("(bytevector-append #u8(1) #u8() #u8(2 3))"), "#u8(1 2 3)");
+        assert_eq!(eval("(define b (make-bytevector 4 0)) (bytevector-copy! b 1 #u8(7 8 9) 1) b"), "#u8(0 8 9 0)");
+        assert_eq!(eval("(bytevector-copy! (make-bytevector 1) 0 #u8(1 2))"), "index out of range");
+    }
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(eval("(string->utf8 \"aλ\")"), "#u8(97 206 187)");
+        assert_eq!(eval("(utf8->string #u8(97 206 187))"), "\"aλ\"");
+        assert_eq!(eval("(utf8->string #u8(206))"), "invalid UTF-8");
+        assert_eq!(eval("(string->utf8 \"aλb\" 1 2)"), "#u8(206 187)");
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(eval("(bytevector-u16-ref #u8(1 2) 0 (quote big))"), "258");
+        assert_eq!(eval("(bytevector-u16-ref #u8(1 2) 0 (quote little))"), "513");
+        assert_eq!(eval("(bytevector-s16-ref #u8(255 254) 0 (quote big))"), "-2");
+        assert_eq!(eval("(bytevector-u32-ref #u8(0 0 0 0 1) 1 (quote little))"), "16777216");
+        assert_eq!(eval("(bytevector-u32-ref #u8(0 0 0 1) 1 (quote big))"), "index out of range");
+        assert_eq!(eval("(define b (make-bytevector 4 0)) (bytevector-s32-set! b 0 -2 (quote little)) b"),
+            "#u8(254 255 255 255)");
+        assert_eq!(eval("(bytevector-u16-set! (make-bytevector 2) 0 65536 (quote big))"), "value out of range");
+        assert_eq!(eval("(bytevector-u16-ref #u8(1 2) 0 (quote middle))"), "endianness must be big or little");
+    }
+}
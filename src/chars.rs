@@ -0,0 +1,138 @@
+use std::convert::TryFrom;
+
+use crate::interpreter::Interpreter;
+use crate::native::Arity;
+use crate::sexpr::SExpr;
+
+// The R7RS character procedures. Characters are Unicode scalar values held
+// directly in `SExpr::Char`.
+
+type Comparison = fn(&char, &char) -> bool;
+
+const COMPARISONS: &[(&str, &str, Comparison)] = &[
+    ("char=?", "char-ci=?", char::eq),
+    ("char<?", "char-ci<?", char::lt),
+    ("char>?", "char-ci>?", char::gt),
+    ("char<=?", "char-ci<=?", char::le),
+    ("char>=?", "char-ci>=?", char::ge),
+];
+
+type Predicate = fn(char) -> bool;
+
+const PREDICATES: &[(&str, Predicate)] = &[
+    ("char-alphabetic?", char::is_alphabetic),
+    ("char-numeric?", char::is_numeric),
+    ("char-whitespace?", char::is_whitespace),
+    ("char-upper-case?", char::is_uppercase),
+    ("char-lower-case?", char::is_lowercase),
+];
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("char?", 1, |_, args| {
+        Ok(matches!(args[0], SExpr::Char(_)))
+    });
+    for &(name, pred) in PREDICATES {
+        interpreter.define_builtin(name, 1, move |interp, args| {
+            Ok(pred(interp.from_sexpr(args[0])?))
+        });
+    }
+    interpreter.define_builtin("char->integer", 1, |interp, args| {
+        let c: char = interp.from_sexpr(args[0])?;
+        Ok(c as i64)
+    });
+    interpreter.define_builtin("integer->char", 1, |interp, args| {
+        let k: i64 = interp.from_sexpr(args[0])?;
+        u32::try_from(k).ok().and_then(char::from_u32).ok_or("invalid code point")
+    });
+    interpreter.define_builtin("digit-value", 1, |interp, args| {
+        let c: char = interp.from_sexpr(args[0])?;
+        Ok(match c.to_digit(10) {
+            Some(d) => SExpr::Int(d.into()),
+            None => SExpr::Bool(false),
+        })
+    });
+    for &(name, ci_name, cmp) in COMPARISONS {
+        interpreter.define_builtin(name, Arity::AtLeast(1), move |interp, args| {
+            let chars: Vec<char> = interp.args(args)?;
+            Ok(chars.windows(2).all(|w| cmp(&w[0], &w[1])))
+        });
+        interpreter.define_builtin(ci_name, Arity::AtLeast(1), move |interp, args| {
+            let chars: Vec<char> = interp.args(args)?;
+            let chars: Vec<char> = chars.into_iter().map(downcase).collect();
+            Ok(chars.windows(2).all(|w| cmp(&w[0], &w[1])))
+        });
+    }
+    interpreter.define_builtin("char-upcase", 1, |interp, args| {
+        Ok(upcase(interp.from_sexpr(args[0])?))
+    });
+    interpreter.define_builtin("char-downcase", 1, |interp, args| {
+        Ok(downcase(interp.from_sexpr(args[0])?))
+    });
+    interpreter.define_builtin("char-foldcase", 1, |interp, args| {
+        Ok(downcase(interp.from_sexpr(args[0])?))
+    });
+}
+
+// Case mappings that would turn one character into several, like 'ß' to
+// "SS", leave the character unchanged.
+fn single(mut mapped: impl Iterator<Item = char>, c: char) -> char {
+    match (mapped.next(), mapped.next()) {
+        (Some(m), None) => m,
+        _ => c,
+    }
+}
+
+fn upcase(c: char) -> char {
+    single(c.to_uppercase(), c)
+}
+
+fn downcase(c: char) -> char {
+    single(c.to_lowercase(), c)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_printing() {
+        assert_eq!(eval("#\\a"), "#\\a");
+        assert_eq!(eval("#\\x3bb"), "#\\λ");
+        assert_eq!(eval("#\\space"), "#\\space");
+        assert_eq!(eval("(integer->char 10)"), "#\\newline");
+        assert_eq!(eval("(integer->char 1)"), "#\\x1");
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert_eq!(eval("(char? #\\a)"), "true");
+        assert_eq!(eval("(char? \"a\")"), "false");
+        assert_eq!(eval("(char-alphabetic? #\\λ)"), "true");
+        assert_eq!(eval("(char-numeric? #\\7)"), "true");
+        assert_eq!(eval("(char-whitespace? #\\tab)"), "true");
+        assert_eq!(eval("(char-upper-case? #\\a)"), "false");
+        assert_eq!(eval("(char-alphabetic? 1)"), "type error: expected character");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(eval("(char->integer #\\λ)"), "955");
+        assert_eq!(eval("(integer->char 955)"), "#\\λ");
+        assert_eq!(eval("(integer->char 55296)"), "invalid code point");
+        assert_eq!(eval("(integer->char -1)"), "invalid code point");
+        assert_eq!(eval("(digit-value #\\7)"), "7");
+        assert_eq!(eval("(digit-value #\\a)"), "false");
+        assert_eq!(eval("(char-upcase #\\λ)"), "#\\Λ");
+        assert_eq!(eval("(char-downcase #\\A)"), "#\\a");
+        assert_eq!(eval("(char-upcase #\\ß)"), "#\\ß");
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(eval("(char<? #\\a #\\b #\\c)"), "true");
+        assert_eq!(eval("(char<? #\\a #\\c #\\b)"), "false");
+        assert_eq!(eval("(char=? #\\a #\\A)"), "false");
+        assert_eq!(eval("(char-ci=? #\\a #\\A)"), "true");
+        assert_eq!(eval("(char>=? #\\b #\\b #\\a)"), "true");
+    }
+}
@@ -0,0 +1,168 @@
+use crate::sexpr::{SExpr, Memory, Object};
+use crate::symbol::Symbol;
+use crate::numbers::Number;
+
+/// Rust values that can be turned into Scheme values.
+pub trait ToSExpr {
+    fn to_sexpr(self, mem: &mut Memory) -> SExpr;
+}
+
+/// Rust values that can be read back out of Scheme values.
+pub trait FromSExpr: Sized {
+    fn from_sexpr(e: SExpr, mem: &Memory) -> Result<Self, &'static str>;
+}
+
+impl ToSExpr for SExpr {
+    fn to_sexpr(self, _: &mut Memory) -> SExpr { self }
+}
+
+impl ToSExpr for i64 {
+    fn to_sexpr(self, _: &mut Memory) -> SExpr { SExpr::Int(self) }
+}
+
+impl ToSExpr for i32 {
+    fn to_sexpr(self, _: &mut Memory) -> SExpr { SExpr::Int(self.into()) }
+}
+
+impl ToSExpr for f64 {
+    fn to_sexpr(self, _: &mut Memory) -> SExpr { SExpr::Float(self) }
+}
+
+impl ToSExpr for Number {
+    fn to_sexpr(self, mem: &mut Memory) -> SExpr {
+        match self {
+            Number::Int(i) => SExpr::Int(i),
+            Number::Big(b) => match Number::from_big(b) {
+                Number::Big(b) => mem.alloc(Object::BigInt(b)),
+                small => small.to_sexpr(mem),
+            },
+            Number::Ratio(r) => mem.alloc(Object::Rational(r)),
+            Number::Float(f) => SExpr::Float(f),
+        }
+    }
+}
+
+impl ToSExpr for bool {
+    fn to_sexpr(self, _: &mut Memory) -> SExpr { SExpr::Bool(self) }
+}
+
+impl ToSExpr for char {
+    fn to_sexpr(self, _: &mut Memory) -> SExpr { SExpr::Char(self) }
+}
+
+impl ToSExpr for &str {
+    fn to_sexpr(self, mem: &mut Memory) -> SExpr { mem.alloc(Object::Str(self.chars().collect())) }
+}
+
+impl ToSExpr for String {
+    fn to_sexpr(self, mem: &mut Memory) -> SExpr { mem.alloc(Object::Str(self.chars().collect())) }
+}
+
+impl ToSExpr for Symbol {
+    fn to_sexpr(self, _: &mut Memory) -> SExpr { SExpr::Sym(self) }
+}
+
+impl<T: ToSExpr> ToSExpr for Vec<T> {
+    fn to_sexpr(self, mem: &mut Memory) -> SExpr {
+        let vec = self.into_iter().map(|x| x.to_sexpr(mem)).collect();
+        mem.list_from_vec(vec)
+    }
+}
+
+impl FromSExpr for SExpr {
+    fn from_sexpr(e: SExpr, _: &Memory) -> Result<Self, &'static str> { Ok(e) }
+}
+
+impl FromSExpr for i64 {
+    fn from_sexpr(e: SExpr, _: &Memory) -> Result<Self, &'static str> {
+        match e {
+            SExpr::Int(i) => Ok(i),
+            _ => Err("type error: expected integer"),
+        }
+    }
+}
+
+impl FromSExpr for f64 {
+    fn from_sexpr(e: SExpr, mem: &Memory) -> Result<Self, &'static str> {
+        Ok(Number::from_sexpr(e, mem)?.to_f64())
+    }
+}
+
+impl FromSExpr for Number {
+    fn from_sexpr(e: SExpr, mem: &Memory) -> Result<Self, &'static str> {
+        match e {
+            SExpr::Int(i) => Ok(Number::Int(i)),
+            SExpr::Float(f) => Ok(Number::Float(f)),
+            _ => match mem.deref(e) {
+                Some(Object::BigInt(b)) => Ok(Number::Big(b.clone())),
+                Some(Object::Rational(r)) => Ok(Number::Ratio(r.clone())),
+                _ => Err("type error: expected number"),
+            },
+        }
+    }
+}
+
+impl FromSExpr for bool {
+    fn from_sexpr(e: SExpr, _: &Memory) -> Result<Self, &'static str> {
+        match e {
+            SExpr::Bool(b) => Ok(b),
+            _ => Err("type error: expected boolean"),
+        }
+    }
+}
+
+impl FromSExpr for char {
+    fn from_sexpr(e: SExpr, _: &Memory) -> Result<Self, &'static str> {
+        match e {
+            SExpr::Char(c) => Ok(c),
+            _ => Err("type error: expected character"),
+        }
+    }
+}
+
+impl FromSExpr for String {
+    fn from_sexpr(e: SExpr, mem: &Memory) -> Result<Self, &'static str> {
+        mem.str_ref(e).map(|s| s.iter().collect()).or(Err("type error: expected string"))
+    }
+}
+
+impl FromSExpr for Symbol {
+    fn from_sexpr(e: SExpr, _: &Memory) -> Result<Self, &'static str> {
+        match e {
+            SExpr::Sym(s) => Ok(s),
+            _ => Err("type error: expected symbol"),
+        }
+    }
+}
+
+impl<T: FromSExpr> FromSExpr for Vec<T> {
+    fn from_sexpr(e: SExpr, mem: &Memory) -> Result<Self, &'static str> {
+        let vec = mem.vec_from_list(e).or(Err("type error: expected list"))?;
+        vec.into_iter().map(|x| T::from_sexpr(x, mem)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut mem = Memory::new(100);
+        let e = 5i64.to_sexpr(&mut mem);
+        assert_eq!(e, SExpr::Int(5));
+        assert_eq!(i64::from_sexpr(e, &mem), Ok(5));
+        assert_eq!(f64::from_sexpr(e, &mem), Ok(5.0));
+        assert_eq!(bool::from_sexpr(e, &mem), Err("type error: expected boolean"));
+
+        let e = "abc".to_sexpr(&mut mem);
+        assert_eq!(String::from_sexpr(e, &mem), Ok("abc".to_string()));
+        assert_eq!(Symbol::from_sexpr(e, &mem), Err("type error: expected symbol"));
+
+        let e = vec![vec![1, 2], vec![3]].to_sexpr(&mut mem);
+        assert_eq!(mem.to_string(e), "((1 2) (3))");
+        assert_eq!(Vec::<Vec<i64>>::from_sexpr(e, &mem), Ok(vec![vec![1, 2], vec![3]]));
+        assert_eq!(Vec::<i64>::from_sexpr(e, &mem), Err("type error: expected integer"));
+        assert_eq!(Vec::<i64>::from_sexpr(SExpr::Int(1), &mem), Err("type error: expected list"));
+    }
+}
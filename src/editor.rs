@@ -0,0 +1,504 @@
+use std::fs;
+use std::io::{self, Read, Write, BufRead};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use rscheme::{Scanner, Token};
+
+// A small line editor for the REPL. The terminal is put into raw mode with
+// `stty` while a line is read, so no platform bindings are needed, and is
+// back in cooked mode while the line is evaluated, so ^C interrupts it.
+// When stdin is not a terminal we fall back to reading plain lines.
+
+const HISTORY_LIMIT: usize = 1000;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Key {
+    Char(char),
+    Enter, Tab, Backspace, Delete,
+    Left, Right, Up, Down, Home, End,
+    KillToEnd, KillToStart, Clear,
+    Interrupt, Eof,
+    Unknown,
+}
+
+fn read_byte<R: Read>(input: &mut R) -> io::Result<Option<u8>> {
+    let mut buf = [0u8; 1];
+    loop {
+        match input.read(&mut buf) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(buf[0])),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+pub fn read_key<R: Read>(input: &mut R) -> io::Result<Option<Key>> {
+    let byte = match read_byte(input)? {
+        Some(b) => b,
+        None => return Ok(None),
+    };
+    let key = match byte {
+        1 => Key::Home,
+        2 => Key::Left,
+        3 => Key::Interrupt,
+        4 => Key::Eof,
+        5 => Key::End,
+        6 => Key::Right,
+        8 | 127 => Key::Backspace,
+        9 => Key::Tab,
+        10 | 13 => Key::Enter,
+        11 => Key::KillToEnd,
+        12 => Key::Clear,
+        14 => Key::Down,
+        16 => Key::Up,
+        21 => Key::KillToStart,
+        27 => read_escape(input)?,
+        b if b < 32 => Key::Unknown,
+        b => read_utf8(b, input)?,
+    };
+    Ok(Some(key))
+}
+
+fn read_escape<R: Read>(input: &mut R) -> io::Result<Key> {
+    match read_byte(input)? {
+        Some(b'[') | Some(b'O') => (),
+        _ => return Ok(Key::Unknown),
+    }
+    let mut param = 0;
+    loop {
+        match read_byte(input)? {
+            Some(b) if b.is_ascii_digit() => param = param * 10 + (b - b'0') as usize,
+            Some(b';') => param = 0,
+            Some(b'A') => return Ok(Key::Up),
+            Some(b'B') => return Ok(Key::Down),
+            Some(b'C') => return Ok(Key::Right),
+            Some(b'D') => return Ok(Key::Left),
+            Some(b'H') => return Ok(Key::Home),
+            Some(b'F') => return Ok(Key::End),
+            Some(b'~') => return Ok(match param {
+                1 | 7 => Key::Home,
+                3 => Key::Delete,
+                4 | 8 => Key::End,
+                _ => Key::Unknown,
+            }),
+            _ => return Ok(Key::Unknown),
+        }
+    }
+}
+
+fn read_utf8<R: Read>(lead: u8, input: &mut R) -> io::Result<Key> {
+    let len = match lead {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Key::Unknown),
+    };
+    let mut bytes = vec![lead];
+    for _ in 1..len {
+        match read_byte(input)? {
+            Some(b) => bytes.push(b),
+            None => return Ok(Key::Unknown),
+        }
+    }
+    match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+        Some(ch) => Ok(Key::Char(ch)),
+        None => Ok(Key::Unknown),
+    }
+}
+
+/// Index of the '(' matched by the ')' at `close`. The line is scanned like
+/// source, so parens in strings, characters and comments do not count.
+/// Returns `None` if the paren is opened on an earlier line.
+pub fn matching_paren(chars: &[char], close: usize) -> Option<usize> {
+    let source: String = chars[..=close].iter().collect();
+    let tokens = Scanner::new(&source).scan_tokens_with_offsets().ok()?;
+    match tokens.last() {
+        Some(&(Token::ClosedParen, at)) if at == source.len() - 1 => (),
+        _ => return None,
+    }
+    let mut open = Vec::new();
+    for &(token, at) in &tokens[..tokens.len() - 1] {
+        match token {
+            Token::OpenParen | Token::OpenVector | Token::OpenBytevector => open.push(at),
+            Token::ClosedParen => { open.pop(); }
+            _ => (),
+        }
+    }
+    let at = open.pop()?;
+    // the paren of `#(` and `#u8(` comes after the prefix
+    let paren = at + source[at..].find('(')?;
+    Some(source[..paren].chars().count())
+}
+
+fn is_delimiter(ch: char) -> bool {
+    ch.is_whitespace() || ch == '(' || ch == ')' || ch == '"'
+}
+
+/// Start of the identifier that ends at `pos`.
+pub fn word_start(chars: &[char], pos: usize) -> usize {
+    let mut start = pos;
+    while start > 0 && !is_delimiter(chars[start - 1]) {
+        start -= 1;
+    }
+    start
+}
+
+/// Longest prefix shared by all of `candidates`.
+pub fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix: Vec<char> = match candidates.first() {
+        Some(first) => first.chars().collect(),
+        None => return String::new(),
+    };
+    for candidate in &candidates[1..] {
+        let shared = prefix.iter().zip(candidate.chars()).take_while(|&(&a, b)| a == b).count();
+        prefix.truncate(shared);
+    }
+    prefix.into_iter().collect()
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct LineBuffer {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl LineBuffer {
+    pub fn new(s: &str) -> Self {
+        let chars: Vec<char> = s.chars().collect();
+        let pos = chars.len();
+        LineBuffer { chars, pos }
+    }
+
+    pub fn chars(&self) -> &[char] { &self.chars }
+
+    pub fn pos(&self) -> usize { self.pos }
+
+    pub fn is_empty(&self) -> bool { self.chars.is_empty() }
+
+    pub fn insert(&mut self, ch: char) {
+        self.chars.insert(self.pos, ch);
+        self.pos += 1;
+    }
+
+    pub fn backspace(&mut self) {
+        if self.pos > 0 {
+            self.pos -= 1;
+            self.chars.remove(self.pos);
+        }
+    }
+
+    pub fn delete(&mut self) {
+        if self.pos < self.chars.len() {
+            self.chars.remove(self.pos);
+        }
+    }
+
+    pub fn left(&mut self) {
+        if self.pos > 0 { self.pos -= 1; }
+    }
+
+    pub fn right(&mut self) {
+        if self.pos < self.chars.len() { self.pos += 1; }
+    }
+
+    pub fn home(&mut self) { self.pos = 0; }
+
+    pub fn end(&mut self) { self.pos = self.chars.len(); }
+
+    pub fn kill_to_end(&mut self) {
+        self.chars.truncate(self.pos);
+    }
+
+    pub fn kill_to_start(&mut self) {
+        self.chars.drain(..self.pos);
+        self.pos = 0;
+    }
+
+    pub fn as_string(&self) -> String {
+        self.chars.iter().collect()
+    }
+}
+
+/// Redraws the prompt and line, leaving the cursor at the edit position.
+/// The char at `highlight`, if any, is drawn in inverse video.
+pub fn render<W: Write>(out: &mut W, prompt: &str, line: &LineBuffer, highlight: Option<usize>) -> io::Result<()> {
+    let mut s = format!("\r{}", prompt);
+    for (i, &ch) in line.chars().iter().enumerate() {
+        if Some(i) == highlight {
+            s.push_str("\x1b[7m");
+            s.push(ch);
+            s.push_str("\x1b[0m");
+        } else {
+            s.push(ch);
+        }
+    }
+    s.push_str("\x1b[K\r");
+    let column = prompt.chars().count() + line.pos();
+    if column > 0 {
+        s.push_str(&format!("\x1b[{}C", column));
+    }
+    out.write_all(s.as_bytes())?;
+    out.flush()
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
+
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> Option<RawMode> {
+        let saved = stty(&["-g"])?;
+        // output processing stays on so what is printed between lines
+        // still starts at the left margin
+        stty(&["raw", "-echo", "opost"])?;
+        Some(RawMode { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[&self.saved]);
+    }
+}
+
+pub struct Editor {
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+    // whether stdin is a terminal that `stty` can put into raw mode
+    terminal: bool,
+}
+
+impl Editor {
+    pub fn new(history_file: Option<PathBuf>) -> Self {
+        let history = match history_file.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(contents) => contents.lines().map(|l| l.to_string()).collect(),
+            None => Vec::new(),
+        };
+        Editor { history, history_file, terminal: stty(&["-g"]).is_some() }
+    }
+
+    pub fn add_history(&mut self, entry: &str) {
+        let entry = entry.trim();
+        if entry.is_empty() || self.history.last().map(|l| l.as_str()) == Some(entry) {
+            return;
+        }
+        self.history.push(entry.to_string());
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+    }
+
+    pub fn save_history(&self) -> io::Result<()> {
+        match self.history_file {
+            Some(ref path) => {
+                let mut contents = self.history.join("\n");
+                contents.push('\n');
+                fs::write(path, contents)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Reads one line, starting with `initial` already in the buffer.
+    /// `complete` maps an identifier prefix to the candidates offered on Tab.
+    /// Returns `Ok(None)` at end of input and an `Interrupted` error on ^C.
+    pub fn read_line(&mut self, prompt: &str, initial: &str, complete: &dyn Fn(&str) -> Vec<String>) -> io::Result<Option<String>> {
+        // the terminal is restored when `raw` is dropped, before returning
+        let raw = if self.terminal { RawMode::enable() } else { None };
+        match raw {
+            Some(_raw) => self.edit(prompt, initial, complete),
+            None => {
+                let mut line = String::new();
+                if io::stdin().lock().read_line(&mut line)? == 0 {
+                    Ok(None)
+                } else {
+                    Ok(Some(line.trim_end_matches(['\n', '\r']).to_string()))
+                }
+            }
+        }
+    }
+
+    fn edit(&mut self, prompt: &str, initial: &str, complete: &dyn Fn(&str) -> Vec<String>) -> io::Result<Option<String>> {
+        let stdin = io::stdin();
+        let mut input = stdin.lock();
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+
+        let mut line = LineBuffer::new(initial);
+        let mut history_index = self.history.len();
+        let mut draft = String::new();
+        let mut highlight = None;
+        loop {
+            render(&mut out, prompt, &line, highlight)?;
+            highlight = None;
+            let key = match read_key(&mut input)? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            match key {
+                Key::Char(ch) => {
+                    line.insert(ch);
+                    if ch == ')' {
+                        highlight = matching_paren(line.chars(), line.pos() - 1);
+                    }
+                }
+                Key::Enter => {
+                    render(&mut out, prompt, &line, None)?;
+                    out.write_all(b"\r\n")?;
+                    return Ok(Some(line.as_string()));
+                }
+                Key::Eof => if line.is_empty() {
+                    out.write_all(b"\r\n")?;
+                    return Ok(None);
+                } else {
+                    line.delete();
+                }
+                Key::Interrupt => {
+                    out.write_all(b"^C\r\n")?;
+                    return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+                }
+                Key::Up => if history_index > 0 {
+                    if history_index == self.history.len() {
+                        draft = line.as_string();
+                    }
+                    history_index -= 1;
+                    line = LineBuffer::new(&self.history[history_index]);
+                }
+                Key::Down => if history_index < self.history.len() {
+                    history_index += 1;
+                    line = match self.history.get(history_index) {
+                        Some(entry) => LineBuffer::new(entry),
+                        None => LineBuffer::new(&draft),
+                    };
+                }
+                Key::Backspace => line.backspace(),
+                Key::Delete => line.delete(),
+                Key::Left => line.left(),
+                Key::Right => line.right(),
+                Key::Home => line.home(),
+                Key::End => line.end(),
+                Key::KillToEnd => line.kill_to_end(),
+                Key::KillToStart => line.kill_to_start(),
+                Key::Tab => {
+                    let start = word_start(line.chars(), line.pos());
+                    let prefix: String = line.chars()[start..line.pos()].iter().collect();
+                    if prefix.is_empty() {
+                        continue;
+                    }
+                    let candidates = complete(&prefix);
+                    let common = common_prefix(&candidates);
+                    if common.chars().count() > prefix.chars().count() {
+                        common.chars().skip(prefix.chars().count()).for_each(|ch| line.insert(ch));
+                    } else if candidates.len() > 1 {
+                        out.write_all(format!("\r\n{}\r\n", candidates.join("  ")).as_bytes())?;
+                    }
+                }
+                Key::Clear => out.write_all(b"\x1b[H\x1b[2J")?,
+                Key::Unknown => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(bytes: &[u8]) -> Vec<Key> {
+        let mut input = bytes;
+        let mut keys = Vec::new();
+        while let Some(key) = read_key(&mut input).unwrap() {
+            keys.push(key);
+        }
+        keys
+    }
+
+    fn chars(s: &str) -> Vec<char> { s.chars().collect() }
+
+    #[test]
+    fn test_read_key() {
+        assert_eq!(keys(b"a("), vec![Key::Char('a'), Key::Char('(')]);
+        assert_eq!(keys(b"\r\x7f\t"), vec![Key::Enter, Key::Backspace, Key::Tab]);
+        assert_eq!(keys(b"\x1b[A\x1b[B\x1b[C\x1b[D"), vec![Key::Up, Key::Down, Key::Right, Key::Left]);
+        assert_eq!(keys(b"\x1b[H\x1b[F\x1bOH\x1b[3~"), vec![Key::Home, Key::End, Key::Home, Key::Delete]);
+        assert_eq!(keys("λ".as_bytes()), vec![Key::Char('λ')]);
+        assert_eq!(keys(b"\x03\x04"), vec![Key::Interrupt, Key::Eof]);
+    }
+
+    #[test]
+    fn test_line_buffer() {
+        let mut line = LineBuffer::new("(+ 1)");
+        line.left();
+        line.insert(' ');
+        line.insert('2');
+        assert_eq!(line.as_string(), "(+ 1 2)");
+        line.home();
+        line.delete();
+        assert_eq!(line.as_string(), "+ 1 2)");
+        line.end();
+        line.backspace();
+        assert_eq!(line.as_string(), "+ 1 2");
+        line.home();
+        line.right();
+        line.kill_to_end();
+        assert_eq!(line.as_string(), "+");
+        line.kill_to_start();
+        assert!(line.is_empty());
+    }
+
+    #[test]
+    fn test_matching_paren() {
+        assert_eq!(matching_paren(&chars("(a)"), 2), Some(0));
+        assert_eq!(matching_paren(&chars("(a (b) c)"), 5), Some(3));
+        assert_eq!(matching_paren(&chars("(a (b) c)"), 8), Some(0));
+        assert_eq!(matching_paren(&chars("(a \"(\" b)"), 8), Some(0));
+        assert_eq!(matching_paren(&chars("a b)"), 3), None);
+        assert_eq!(matching_paren(&chars("(f #\\( #\\))"), 10), Some(0));
+        assert_eq!(matching_paren(&chars("(f #\\)"), 5), None);
+        assert_eq!(matching_paren(&chars("(a ; (b)"), 7), None);
+        assert_eq!(matching_paren(&chars("(\"λ\" #(1) #u8(2))"), 8), Some(6));
+        assert_eq!(matching_paren(&chars("(\"λ\" #(1) #u8(2))"), 15), Some(13));
+        assert_eq!(matching_paren(&chars("(\"λ\" #(1) #u8(2))"), 16), Some(0));
+        assert_eq!(matching_paren(&chars("(a \"b)"), 5), None);
+    }
+
+    #[test]
+    fn test_completion_helpers() {
+        assert_eq!(word_start(&chars("(set-c"), 6), 1);
+        assert_eq!(word_start(&chars("(f (ca x)"), 6), 4);
+        assert_eq!(word_start(&chars("(f "), 3), 3);
+        assert_eq!(word_start(&chars("abc"), 3), 0);
+
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(common_prefix(&strings(&["set-car!", "set-cdr!"])), "set-c");
+        assert_eq!(common_prefix(&strings(&["car"])), "car");
+        assert_eq!(common_prefix(&strings(&["car", "cdr", "cons"])), "c");
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut out = Vec::new();
+        let mut line = LineBuffer::new("(a)");
+        line.left();
+        render(&mut out, "> ", &line, Some(0)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\r> \x1b[7m(\x1b[0ma)\x1b[K\r\x1b[4C");
+    }
+}
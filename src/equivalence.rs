@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::interpreter::Interpreter;
+use crate::sexpr::{SExpr, Memory, Object};
+
+// The equivalence predicates and the hash functions consistent with them.
+// Hashes of `SExpr::Ref` values other than numbers depend on addresses, so
+// they are only valid until `Memory::relocations` changes.
+
+// How deep `hash` looks into pairs and vectors; deeper structure only
+// affects equality, which keeps hashing cheap and finite on cycles.
+const HASH_DEPTH: usize = 4;
+
+/// Identity: the same immediate value or the same memory cell.
+pub fn eq(a: SExpr, b: SExpr) -> bool {
+    match (a, b) {
+        (SExpr::Float(x), SExpr::Float(y)) => x.to_bits() == y.to_bits(),
+        _ => a == b,
+    }
+}
+
+/// Like `eq`, but bignums and rationals are compared by value. Exactness
+/// matters, so `2` and `2.0` are different.
+pub fn eqv(mem: &Memory, a: SExpr, b: SExpr) -> bool {
+    match (a, b) {
+        (SExpr::Ref(x), SExpr::Ref(y)) if x != y => match (mem.get(x), mem.get(y)) {
+            (Object::BigInt(m), Object::BigInt(n)) => m == n,
+            (Object::Rational(p), Object::Rational(q)) => p == q,
+            _ => false,
+        },
+        _ => eq(a, b),
+    }
+}
+
+/// Structural equality through pairs, strings, vectors and bytevectors.
+/// Pairs of objects already being compared are assumed equal, so cyclic
+/// structures terminate.
+pub fn equal(mem: &Memory, a: SExpr, b: SExpr) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![(a, b)];
+    while let Some((a, b)) = pending.pop() {
+        if eqv(mem, a, b) {
+            continue;
+        }
+        let (x, y) = match (a, b) {
+            (SExpr::Ref(x), SExpr::Ref(y)) => (x, y),
+            _ => return false,
+        };
+        if !seen.insert((x, y)) {
+            continue;
+        }
+        match (mem.get(x), mem.get(y)) {
+            (&Object::Pair(car1, cdr1), &Object::Pair(car2, cdr2)) => {
+                pending.push((cdr1, cdr2));
+                pending.push((car1, car2));
+            }
+            (Object::Vector(v), Object::Vector(w)) if v.len() == w.len() => {
+                pending.extend(v.iter().copied().zip(w.iter().copied()).rev());
+            }
+            (Object::Str(s), Object::Str(t)) if s == t => (),
+            (Object::Bytevector(s), Object::Bytevector(t)) if s == t => (),
+            (Object::Host(h), Object::Host(k)) if h.equals(k) => (),
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// A hash of `e` consistent with `eqv`, or with `equal` when `structural`.
+pub fn hash(mem: &Memory, e: SExpr, structural: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    if structural {
+        hash_structure(mem, e, &mut hasher, HASH_DEPTH);
+    } else {
+        hash_value(mem, e, &mut hasher);
+    }
+    hasher.finish()
+}
+
+fn hash_value(mem: &Memory, e: SExpr, hasher: &mut DefaultHasher) {
+    std::mem::discriminant(&e).hash(hasher);
+    match e {
+        SExpr::Nil | SExpr::Eof | SExpr::Unspecified => (),
+        SExpr::Int(i) => i.hash(hasher),
+        SExpr::Float(f) => f.to_bits().hash(hasher),
+        SExpr::Bool(b) => b.hash(hasher),
+        SExpr::Char(c) => c.hash(hasher),
+        SExpr::Sym(sym) => sym.hash(hasher),
+        SExpr::Ref(addr) => match mem.get(addr) {
+            Object::BigInt(b) => b.hash(hasher),
+            Object::Rational(r) => r.hash(hasher),
+            _ => addr.hash(hasher),
+        },
+    }
+}
+
+fn hash_structure(mem: &Memory, e: SExpr, hasher: &mut DefaultHasher, depth: usize) {
+    let addr = match e {
+        SExpr::Ref(addr) => addr,
+        _ => return hash_value(mem, e, hasher),
+    };
+    if depth == 0 {
+        return;
+    }
+    match mem.get(addr) {
+        &Object::Pair(car, cdr) => {
+            "pair".hash(hasher);
+            hash_structure(mem, car, hasher, depth - 1);
+            hash_structure(mem, cdr, hasher, depth - 1);
+        }
+        Object::Vector(v) => {
+            v.len().hash(hasher);
+            for &e in v.iter().take(HASH_DEPTH) {
+                hash_structure(mem, e, hasher, depth - 1);
+            }
+        }
+        Object::Str(s) => s.hash(hasher),
+        Object::Bytevector(bytes) => bytes.hash(hasher),
+        // host objects may define their own equality, so they cannot be
+        // told apart here
+        Object::Host(host) => host.type_name().hash(hasher),
+        _ => hash_value(mem, e, hasher),
+    }
+}
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("eq?", 2, |_, args| Ok(eq(args[0], args[1])));
+    interpreter.define_builtin("eqv?", 2, |interp, args| Ok(eqv(interp.memory(), args[0], args[1])));
+    interpreter.define_builtin("equal?", 2, |interp, args| Ok(equal(interp.memory(), args[0], args[1])));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_equal() {
+        assert_eq!(eval("(equal? (list 1 (vector 2 \"x\")) (list 1 (vector 2 \"x\")))"), "true");
+        assert_eq!(eval("(equal? (list 1 2) (list 1 2 3))"), "false");
+        assert_eq!(eval("(eqv? \"x\" \"x\")"), "false");
+        assert_eq!(eval("(define x (list 1)) (eq? x x)"), "true");
+    }
+
+    #[test]
+    fn test_eqv_numbers() {
+        assert_eq!(eval("(eqv? 100000000000000000000 100000000000000000000)"), "true");
+        assert_eq!(eval("(eqv? 1/3 (/ 2 6))"), "true");
+        assert_eq!(eval("(eqv? 1/3 1/4)"), "false");
+        assert_eq!(eval("(eqv? 2 2.0)"), "false");
+        assert_eq!(eval("(eqv? 0.0 -0.0)"), "false");
+        assert_eq!(eval("(eqv? +nan.0 +nan.0)"), "true");
+        assert_eq!(eval("(eqv? #\\a #\\a)"), "true");
+        assert_eq!(eval("(eq? 1/3 1/3)"), "false");
+        assert_eq!(eval("(equal? (list 1/2 99999999999999999999) (list 2/4 99999999999999999999))"), "true");
+        assert_eq!(eval("(define t (make-hash-table eqv?)) (hash-table-set! t 1/3 1) (hash-table-ref/default t (/ 1 3) 0)"), "1");
+    }
+
+    #[test]
+    fn test_cycles() {
+        let cycle = |name: &str, elements: &str| format!("(define {0} (list {1})) (set-cdr! (last-pair {0}) {0})", name, elements);
+        let last_pair = "(define (last-pair l) (if (null? (cdr l)) l (last-pair (cdr l))))";
+        let eval_cycles = |x: &str, y: &str, test: &str| eval(&format!("{} {} {} {}", last_pair, cycle("x", x), cycle("y", y), test));
+        assert_eq!(eval_cycles("1 2", "1 2", "(equal? x y)"), "true");
+        assert_eq!(eval_cycles("1 2", "1 3", "(equal? x y)"), "false");
+        assert_eq!(eval_cycles("1", "1 1", "(equal? x y)"), "true");
+        assert_eq!(eval_cycles("1 2", "1 2", "(eqv? x y)"), "false");
+        assert_eq!(eval("(define v (vector 1 2)) (vector-set! v 1 v) \
+                         (define w (vector 1 2)) (vector-set! w 1 w) (equal? v w)"), "true");
+        assert_eq!(eval("(define v (vector 1 2)) (vector-set! v 1 v) (equal? v (vector 1 v))"), "true");
+        assert_eq!(eval("(define v (vector 1 2)) (vector-set! v 1 v) (equal? v (vector 2 v))"), "false");
+    }
+
+    #[test]
+    fn test_hash() {
+        let mut interpreter = Interpreter::new(1000);
+        let a = interpreter.eval_string("(list 1 \"two\" #(3))").unwrap();
+        let b = interpreter.eval_string("(list 1 \"two\" #(3))").unwrap();
+        let mem = interpreter.memory();
+        assert_eq!(hash(mem, a, true), hash(mem, b, true));
+        assert_ne!(hash(mem, a, false), hash(mem, b, false));
+        assert_eq!(hash(mem, SExpr::Float(0.5), false), hash(mem, SExpr::Float(0.5), false));
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+use crate::interpreter::Interpreter;
+use crate::native::Arity;
+use crate::sexpr::{SExpr, Object, Environment};
+use crate::symbol::Symbol;
+
+// The procedures that reach back into the evaluator: `eval`, the
+// environments it accepts and the ones for inspecting and extending them.
+// `apply` is a primitive, so that it calls its procedure in tail position.
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("eval", Arity::Between(1, 2), |interp, args| {
+        let env = match args.get(1) {
+            Some(&env) => env,
+            None => interp.interaction_environment(),
+        };
+        let e = interp.expand(args[0])?;
+        interp.eval_in_rooted(e, env)
+    });
+    interpreter.define_builtin("interaction-environment", 0, |interp, _| Ok(interp.interaction_environment()));
+    interpreter.define_builtin("scheme-report-environment", 1, |interp, args| {
+        version(interp, Some(&args[0]))?;
+        interp.report_environment()
+    });
+    // special forms are not bindings, so this is just an empty environment
+    interpreter.define_builtin("null-environment", Arity::Between(0, 1), |interp, args| {
+        version(interp, args.first())?;
+        Ok(interp.memory_mut().alloc(Object::Env(Environment::new(SExpr::Nil))))
+    });
+    // an empty environment whose lookups fall through to `parent`
+    interpreter.define_builtin("make-environment", Arity::Between(0, 1), |interp, args| {
+        let parent = match args.first() {
+            Some(&parent) => environment(interp, parent)?,
+            None => SExpr::Nil,
+        };
+        Ok(interp.memory_mut().alloc(Object::Env(Environment::new(parent))))
+    });
+    interpreter.define_builtin("environment-bound?", 2, |interp, args| {
+        let (env, sym): (SExpr, Symbol) = interp.args(args)?;
+        Ok(interp.memory().env_get(sym, environment(interp, env)?).is_ok())
+    });
+    // every visible name once, innermost frame first
+    interpreter.define_builtin("environment-bound-names", 1, |interp, args| {
+        let env = environment(interp, args[0])?;
+        let mut names = interp.memory().env_names(env).unwrap();
+        let mut seen = HashSet::new();
+        names.retain(|&sym| seen.insert(sym));
+        let names = names.into_iter().map(SExpr::Sym).collect();
+        Ok(interp.memory_mut().list_from_vec(names))
+    });
+    interpreter.define_builtin("environment-lookup", 2, |interp, args| {
+        let (env, sym): (SExpr, Symbol) = interp.args(args)?;
+        interp.memory().env_get(sym, environment(interp, env)?).or(Err("Unbound variable"))
+    });
+    interpreter.define_builtin("environment-define!", 3, |interp, args| {
+        let (env, sym): (SExpr, Symbol) = interp.args(&args[..2])?;
+        let env = environment(interp, env)?;
+        interp.memory_mut().env_insert(env, sym, args[2]).unwrap();
+        Ok(SExpr::Nil)
+    });
+}
+
+fn environment(interp: &Interpreter, e: SExpr) -> Result<SExpr, &'static str> {
+    match interp.memory().deref(e) {
+        Some(Object::Env(_)) => Ok(e),
+        _ => Err("type error: expected environment"),
+    }
+}
+
+// Checks the report version asked for, R5RS or R7RS.
+fn version(interp: &Interpreter, e: Option<&SExpr>) -> Result<(), &'static str> {
+    match e {
+        Some(&e) => match interp.from_sexpr::<i64>(e)? {
+            5 | 7 => Ok(()),
+            _ => Err("unsupported report version"),
+        },
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(eval("(apply + (list 1 2 3))"), "6");
+        assert_eq!(eval("(apply + 1 2 (list 3 4))"), "10");
+        assert_eq!(eval("(apply (lambda (x y) (- x y)) (list 5 3))"), "2");
+        assert_eq!(eval("(apply max 1 (list))"), "1");
+        assert_eq!(eval("(apply + 1 2)"), "type error: expected list");
+        assert_eq!(eval("(apply 1 (list))"), "Applying non procedure");
+        assert_eq!(eval("(define table (list (cons (quote add) +) (cons (quote mul) *))) \
+                         (apply (cdr (assq (quote mul) table)) (list 6 7))"), "42");
+        assert_eq!(eval("(apply)"), "wrong arity");
+        assert_eq!(eval("(define (f n) (apply (lambda (m) (if (= m 0) 0 (f (- m 1)))) (list n))) (f 100000)"), "0");
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("(eval (list (quote +) 1 2))"), "3");
+        assert_eq!(eval("(eval (quote (* 2 3)) (interaction-environment))"), "6");
+        assert_eq!(eval("(eval (quote (define x 5)) (interaction-environment)) x"), "5");
+        assert_eq!(eval("(eval (quote (let ((y 2)) (* y y))) (scheme-report-environment 7))"), "4");
+        assert_eq!(eval("(define x 5) (eval (quote x) (scheme-report-environment 5))"), "Unbound variable");
+        assert_eq!(eval("(define e (scheme-report-environment 7)) (eval (quote (define car cdr)) e) \
+                         (list (car (list 1 2)) (eval (quote (car (list 1 2))) e))"), "(1 (2))");
+        assert_eq!(eval("(eval (quote (if #t 1 2)) (null-environment 5))"), "1");
+        assert_eq!(eval("(eval (quote (car (list 1))) (null-environment))"), "Unbound variable");
+        assert_eq!(eval("(eval 1 2)"), "type error: expected environment");
+        assert_eq!(eval("(scheme-report-environment 6)"), "unsupported report version");
+    }
+
+    #[test]
+    fn test_first_class_environments() {
+        assert_eq!(eval("(eq? (the-environment) (interaction-environment))"), "true");
+        assert_eq!(eval("(define (f x) (the-environment)) (environment-lookup (f 42) (quote x))"), "42");
+        assert!(eval("(define (f x) (the-environment)) (environment-bound-names (f 42))").starts_with("(x "));
+        assert_eq!(eval("(the-environment 1)"), "ill formed the-environment");
+        assert_eq!(eval("(environment-bound? (the-environment) (quote car))"), "true");
+        assert_eq!(eval("(environment-bound? (the-environment) (quote nope))"), "false");
+        assert_eq!(eval("(environment-lookup (the-environment) (quote nope))"), "Unbound variable");
+        assert_eq!(eval("(environment-bound? 1 (quote car))"), "type error: expected environment");
+        assert_eq!(eval("(environment-bound? (the-environment) \"car\")"), "type error: expected symbol");
+    }
+
+    #[test]
+    fn test_sandboxes() {
+        assert_eq!(eval("(environment-bound-names (make-environment))"), "()");
+        assert_eq!(eval("(define e (make-environment)) (environment-define! e (quote a) 1) \
+                         (environment-define! e (quote a) 2) (environment-bound-names e)"), "(a)");
+        assert_eq!(eval("(define sandbox (make-environment (scheme-report-environment 7))) \
+                         (eval (quote (define (double x) (* 2 x))) sandbox) \
+                         (list (eval (quote (double 21)) sandbox) (environment-bound? (the-environment) (quote double)))"),
+                   "(42 false)");
+        assert_eq!(eval("(define plugin (make-environment (the-environment))) \
+                         (environment-define! plugin (quote greeting) \"hi\") \
+                         (define x 1) (eval (quote (list greeting x)) plugin)"), "(\"hi\" 1)");
+        assert_eq!(eval("(define child (make-environment (the-environment))) (define y 5) \
+                         (eval (quote (set! y 6)) child) y"), "6");
+        assert_eq!(eval("(make-environment 1)"), "type error: expected environment");
+    }
+}
@@ -0,0 +1,202 @@
+use crate::sexpr::{SExpr, Memory};
+use crate::symbol::{self, Symbol};
+
+// Derived forms are rewritten into the core forms understood by the
+// interpreter: quote, set!, define, if, begin, lambda and delay-force.
+
+fn sym(s: Symbol) -> SExpr { SExpr::Sym(s) }
+
+/// Rewrites `e` once if it is a derived form, returning `None` otherwise.
+pub fn expand_derived(mem: &mut Memory, e: SExpr) -> Result<Option<SExpr>, &'static str> {
+    match mem.car(e) {
+        Ok(SExpr::Sym(symbol::DEFINE)) => expand_define(mem, e),
+        Ok(SExpr::Sym(symbol::COND)) => expand_cond(mem, e).map(Some),
+        Ok(SExpr::Sym(symbol::LET)) => expand_let(mem, e).map(Some),
+        Ok(SExpr::Sym(symbol::DEFINE_STREAM)) => expand_define_stream(mem, e).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Fully expands `e`, including every subform that will be evaluated.
+pub fn expand(mem: &mut Memory, e: SExpr) -> Result<SExpr, &'static str> {
+    let mut e = e;
+    while let Some(expanded) = expand_derived(mem, e)? {
+        e = expanded;
+    }
+    let form = match mem.vec_from_list(e) {
+        Ok(form) => form,
+        Err(_) => return Ok(e), // atoms, objects and improper lists are left alone
+    };
+    // the number of leading subforms that are not expressions
+    let skip = match form.first() {
+        Some(SExpr::Sym(symbol::QUOTE)) | Some(SExpr::Sym(symbol::DEFINE_RECORD_TYPE))
+        | Some(SExpr::Sym(symbol::THE_ENVIRONMENT)) => return Ok(e),
+        Some(SExpr::Sym(symbol::LAMBDA)) | Some(SExpr::Sym(symbol::DEFINE)) | Some(SExpr::Sym(symbol::SET)) => 2,
+        _ => 0,
+    };
+    let mut expanded = Vec::with_capacity(form.len());
+    for (i, &sub) in form.iter().enumerate() {
+        expanded.push(if i < skip { sub } else { expand(mem, sub)? });
+    }
+    // forms without derived forms in them are shared, not copied
+    if expanded == form {
+        return Ok(e);
+    }
+    Ok(mem.list_from_vec(expanded))
+}
+
+// (define (name . params) body ...) => (define name (lambda params body ...))
+fn expand_define(mem: &mut Memory, e: SExpr) -> Result<Option<SExpr>, &'static str> {
+    let signature = mem.get_nth_ref(e, 1).and_then(|r| mem.car(r)).or(Err("ill formed define"))?;
+    if let SExpr::Sym(_) = signature {
+        return Ok(None);
+    }
+    let name = mem.car(signature).or(Err("ill formed define"))?;
+    let params = mem.cdr(signature).or(Err("ill formed define"))?;
+    let body = mem.get_nth_ref(e, 2).or(Err("ill formed define"))?;
+    let body = mem.cons(params, body);
+    let lambda = mem.cons(sym(symbol::LAMBDA), body);
+    Ok(Some(mem.list_from_vec(vec![sym(symbol::DEFINE), name, lambda])))
+}
+
+// (define-stream (name . params) body ...)
+//     => (define name (lambda params (delay-force (begin body ...))))
+fn expand_define_stream(mem: &mut Memory, e: SExpr) -> Result<SExpr, &'static str> {
+    let signature = mem.get_nth_ref(e, 1).and_then(|r| mem.car(r)).or(Err("ill formed define-stream"))?;
+    let name = mem.car(signature).or(Err("ill formed define-stream"))?;
+    let params = mem.cdr(signature).or(Err("ill formed define-stream"))?;
+    let body = mem.get_nth_ref(e, 2).or(Err("ill formed define-stream"))?;
+    if body == SExpr::Nil {
+        return Err("ill formed define-stream");
+    }
+    let body = mem.cons(sym(symbol::BEGIN), body);
+    let lazy = mem.list_from_vec(vec![sym(symbol::DELAY_FORCE), body]);
+    let lambda = mem.list_from_vec(vec![sym(symbol::LAMBDA), params, lazy]);
+    Ok(mem.list_from_vec(vec![sym(symbol::DEFINE), name, lambda]))
+}
+
+// (cond (test body ...) ... (else body ...)) => (if test (begin body ...) ...)
+fn expand_cond(mem: &mut Memory, e: SExpr) -> Result<SExpr, &'static str> {
+    let clauses = mem.cdr(e).and_then(|c| mem.vec_from_list(c)).or(Err("ill formed cond"))?;
+    let mut acc = None;
+    for &clause in clauses.iter().rev() {
+        let test = mem.car(clause).or(Err("ill formed cond clause"))?;
+        let body = mem.cdr(clause).or(Err("ill formed cond clause"))?;
+        if body == SExpr::Nil {
+            return Err("ill formed cond clause");
+        }
+        let body = mem.cons(sym(symbol::BEGIN), body);
+        acc = Some(match (test, acc) {
+            (SExpr::Sym(symbol::ELSE), None) => body,
+            (SExpr::Sym(symbol::ELSE), Some(_)) => return Err("else clause must be last"),
+            (test, None) => mem.list_from_vec(vec![sym(symbol::IF), test, body]),
+            (test, Some(alternative)) => mem.list_from_vec(vec![sym(symbol::IF), test, body, alternative]),
+        });
+    }
+    match acc {
+        Some(e) => Ok(e),
+        // no clause can match, (if #f #f) evaluates to the unspecified value
+        None => Ok(mem.list_from_vec(vec![sym(symbol::IF), SExpr::Bool(false), SExpr::Bool(false)])),
+    }
+}
+
+// (let ((var init) ...) body ...) => ((lambda (var ...) body ...) init ...)
+// (let name ((var init) ...) body ...)
+//     => ((lambda () (define name (lambda (var ...) body ...)) name) init ...)
+// so the inits are evaluated where name is not bound yet
+fn expand_let(mem: &mut Memory, e: SExpr) -> Result<SExpr, &'static str> {
+    let form = mem.vec_from_list(e).or(Err("ill formed let"))?;
+    let (name, bindings_at) = match form.get(1) {
+        Some(&SExpr::Sym(name)) => (Some(name), 2),
+        Some(_) => (None, 1),
+        None => return Err("ill formed let"),
+    };
+    let bindings = form.get(bindings_at).ok_or("ill formed let")?;
+    let bindings = mem.vec_from_list(*bindings).or(Err("ill formed let"))?;
+    let body = mem.get_nth_ref(e, bindings_at + 1).or(Err("ill formed let"))?;
+    let mut vars = Vec::with_capacity(bindings.len());
+    let mut inits = Vec::with_capacity(bindings.len());
+    for &binding in &bindings {
+        match mem.vec_from_list(binding).or(Err("ill formed let binding"))?[..] {
+            [var @ SExpr::Sym(_), init] => { vars.push(var); inits.push(init); }
+            _ => return Err("ill formed let binding"),
+        }
+    }
+    let params = mem.list_from_vec(vars);
+    let body = mem.cons(params, body);
+    let lambda = mem.cons(sym(symbol::LAMBDA), body);
+    match name {
+        None => {
+            inits.insert(0, lambda);
+            Ok(mem.list_from_vec(inits))
+        }
+        Some(name) => {
+            let define = mem.list_from_vec(vec![sym(symbol::DEFINE), sym(name), lambda]);
+            let thunk = mem.list_from_vec(vec![sym(symbol::LAMBDA), SExpr::Nil, define, sym(name)]);
+            let procd = mem.list_from_vec(vec![thunk]);
+            inits.insert(0, procd);
+            Ok(mem.list_from_vec(inits))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+    use crate::parser::Parser;
+
+    fn expand_str(s: &str) -> String {
+        let mut mem = Memory::new(500);
+        let tokens = Scanner::new(s).scan_tokens().unwrap();
+        let e = Parser::new(tokens, &mut mem).parse().unwrap();
+        match expand(&mut mem, e) {
+            Ok(res) => mem.to_string(res),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_expand_define() {
+        assert_eq!(expand_str("(define (f x) (* x x))"), "(define f (lambda (x) (* x x)))");
+        assert_eq!(expand_str("(define (f) 1 2)"), "(define f (lambda () 1 2))");
+        assert_eq!(expand_str("(define x 1)"), "(define x 1)");
+        assert_eq!(expand_str("(lambda (x) (define (g) x) g)"), "(lambda (x) (define g (lambda () x)) g)");
+    }
+
+    #[test]
+    fn test_expand_cond() {
+        assert_eq!(expand_str("(cond (a 1) (b 2) (else 3))"), "(if a (begin 1) (if b (begin 2) (begin 3)))");
+        assert_eq!(expand_str("(cond (a 1 2))"), "(if a (begin 1 2))");
+        assert_eq!(expand_str("(cond (else 1) (a 2))"), "else clause must be last");
+        assert_eq!(expand_str("(cond (a))"), "ill formed cond clause");
+        assert_eq!(expand_str("(cond)"), "(if false false)");
+    }
+
+    #[test]
+    fn test_expand_let() {
+        assert_eq!(expand_str("(let ((a 1) (b 2)) (+ a b))"), "((lambda (a b) (+ a b)) 1 2)");
+        assert_eq!(expand_str("(let loop ((i 0)) (loop i))"),
+            "(((lambda () (define loop (lambda (i) (loop i))) loop)) 0)");
+        assert_eq!(expand_str("(let ((a)) a)"), "ill formed let binding");
+        assert_eq!(expand_str("(quote (let ((a 1)) a))"), "(quote (let ((a 1)) a))");
+    }
+
+    #[test]
+    fn test_expand_shares_core_forms() {
+        let mut mem = Memory::new(500);
+        let tokens = Scanner::new("(lambda (x) (if x (f x) (quote (cond))))").scan_tokens().unwrap();
+        let e = Parser::new(tokens, &mut mem).parse().unwrap();
+        let allocations = mem.allocations();
+        assert_eq!(expand(&mut mem, e), Ok(e));
+        assert_eq!(mem.allocations(), allocations);
+    }
+
+    #[test]
+    fn test_expand_define_stream() {
+        assert_eq!(expand_str("(define-stream (ones) (stream-cons 1 (ones)))"),
+            "(define ones (lambda () (delay-force (begin (stream-cons 1 (ones))))))");
+        assert_eq!(expand_str("(define-stream (f x))"), "ill formed define-stream");
+        assert_eq!(expand_str("(define-stream f 1)"), "ill formed define-stream");
+    }
+}
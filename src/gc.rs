@@ -0,0 +1,118 @@
+use crate::promises::Promise;
+use crate::records::RecordProcedure;
+use crate::sexpr::{SExpr, Memory, Object};
+
+// A mark and sweep collector. Objects do not move, but every collection is
+// recorded as a relocation, so what is derived from addresses, like the
+// buckets of eq? hash tables, is rebuilt as it would be after compaction.
+
+/// Frees every object born in epoch `since` or later that cannot be reached
+/// from `roots` or from an older object, returning how many were freed.
+/// With `since` 0 only `roots` keep objects alive.
+pub fn collect(mem: &mut Memory, roots: &[SExpr], since: u64) -> usize {
+    let mut marked = vec![false; mem.capacity()];
+    let mut pending = roots.to_vec();
+    pending.extend((0..mem.capacity())
+        .filter(|&addr| mem.born(addr) < since && !matches!(mem.get(addr), Object::Empty(_)))
+        .map(SExpr::Ref));
+    while let Some(e) = pending.pop() {
+        if let SExpr::Ref(addr) = e {
+            if !marked[addr] {
+                marked[addr] = true;
+                push_children(mem.get(addr), &mut pending);
+            }
+        }
+    }
+    let mut freed = 0;
+    for (addr, &live) in marked.iter().enumerate() {
+        if !live && !matches!(mem.get(addr), Object::Empty(_)) {
+            mem.free(addr);
+            freed += 1;
+        }
+    }
+    mem.relocated();
+    freed
+}
+
+// Pushes the values `obj` refers to.
+fn push_children(obj: &Object, pending: &mut Vec<SExpr>) {
+    match *obj {
+        Object::Pair(car, cdr) => pending.extend([car, cdr]),
+        Object::CompoundProcedure(procd) => pending.push(procd),
+        Object::Env(ref env) => {
+            pending.extend(env.values());
+            pending.push(env.enclosing());
+        }
+        Object::Vector(ref v) => pending.extend(v),
+        Object::HashTable(ref table) => {
+            for (k, v) in table.entries() {
+                pending.extend([k, v]);
+            }
+        }
+        Object::Record(ref r) => {
+            pending.push(r.rtype);
+            pending.extend(&r.fields);
+        }
+        Object::RecordProcedure(ref procd) => match *procd {
+            RecordProcedure::Constructor(rtype, _) | RecordProcedure::Predicate(rtype)
+            | RecordProcedure::Accessor(rtype, _) | RecordProcedure::Modifier(rtype, _) => pending.push(rtype),
+        },
+        Object::Promise(promise) => match promise {
+            Promise::Forced(value) => pending.push(value),
+            Promise::Delayed { expr, env, .. } => pending.extend([expr, env]),
+            Promise::Shared(other) => pending.push(other),
+        },
+        Object::BigInt(_) | Object::Rational(_) | Object::PrimitiveProcedure(_) | Object::NativeProcedure(_)
+        | Object::Str(_) | Object::Bytevector(_) | Object::RecordType(_) | Object::Host(_) | Object::Empty(_) => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sexpr::Environment;
+
+    fn i(i: i64) -> SExpr { SExpr::Int(i) }
+
+    #[test]
+    fn test_collect() {
+        let mut mem = Memory::new(100);
+        let kept = mem.list_from_vec(vec![i(1), i(2), i(3)]);
+        mem.list_from_vec(vec![i(4), i(5)]);
+        let v = mem.alloc(Object::Vector(vec![kept]));
+        assert_eq!(collect(&mut mem, &[v], 0), 2);
+        assert_eq!(mem.to_string(v), "#((1 2 3))");
+        assert_eq!(collect(&mut mem, &[], 0), 4);
+        assert_eq!(mem.available(), 99);
+    }
+
+    #[test]
+    fn test_cycles_and_environments() {
+        let mut mem = Memory::new(100);
+        let a = mem.intern("a");
+        let global = mem.alloc(Object::Env(Environment::new(SExpr::Nil)));
+        let pair = mem.cons(i(1), SExpr::Nil);
+        mem.set_cdr(pair, pair).unwrap();
+        let mut local = Environment::new(global);
+        local.insert(a, pair);
+        let local = mem.alloc(Object::Env(local));
+        assert_eq!(collect(&mut mem, &[local], 0), 0);
+        assert_eq!(collect(&mut mem, &[global], 0), 2);
+        assert_eq!(mem.env_get(a, global), Err("Unbound variable"));
+    }
+
+    #[test]
+    fn test_old_objects_are_kept() {
+        let mut mem = Memory::new(100);
+        let old = mem.list_from_vec(vec![i(1), i(2)]);
+        let since = mem.next_epoch();
+        let young = mem.cons(i(3), SExpr::Nil);
+        mem.set_cdr(young, old).unwrap();
+        let four = mem.cons(i(4), SExpr::Nil);
+        mem.set_car(old, four).unwrap();
+        mem.cons(i(5), SExpr::Nil);
+        assert_eq!(collect(&mut mem, &[], since), 2);
+        assert_eq!(mem.to_string(old), "((4) 2)");
+        assert_eq!(collect(&mut mem, &[], 0), 3);
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+
+use crate::equivalence::{self, eqv, equal};
+use crate::interpreter::Interpreter;
+use crate::native::Arity;
+use crate::sexpr::{SExpr, Memory, Object};
+
+/// The equivalence a hash table compares its keys with.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Equivalence {
+    Eqv,
+    Equal,
+}
+
+/// A SRFI-69 hash table stored in `Memory`.
+///
+/// Keys are hashed with `equivalence::hash`, which uses the address of
+/// objects that are compared by identity. The table remembers the value of
+/// `Memory::relocations` its buckets were built under and rehashes every
+/// key before its next lookup if objects have moved since.
+#[derive(PartialEq, Debug)]
+pub struct HashTable {
+    equivalence: Equivalence,
+    buckets: HashMap<u64, Vec<(SExpr, SExpr)>>,
+    len: usize,
+    relocations: u64,
+}
+
+impl HashTable {
+    pub fn new(equivalence: Equivalence) -> Self {
+        HashTable { equivalence, buckets: HashMap::new(), len: 0, relocations: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn entries(&self) -> Vec<(SExpr, SExpr)> {
+        self.buckets.values().flatten().copied().collect()
+    }
+
+    fn hash(&self, mem: &Memory, key: SExpr) -> u64 {
+        equivalence::hash(mem, key, self.equivalence == Equivalence::Equal)
+    }
+
+    fn same(&self, mem: &Memory, a: SExpr, b: SExpr) -> bool {
+        match self.equivalence {
+            Equivalence::Eqv => eqv(mem, a, b),
+            Equivalence::Equal => equal(mem, a, b),
+        }
+    }
+
+    // The bucket `key` belongs in and its position there, if present.
+    fn find(&self, mem: &Memory, key: SExpr) -> (u64, Option<usize>) {
+        let h = self.hash(mem, key);
+        let pos = self.buckets.get(&h).and_then(|bucket| bucket.iter().position(|&(k, _)| self.same(mem, k, key)));
+        (h, pos)
+    }
+}
+
+fn table(mem: &Memory, e: SExpr) -> Result<&HashTable, &'static str> {
+    mem.hash_table_ref(e).or(Err("type error: expected hash table"))
+}
+
+fn table_mut(mem: &mut Memory, e: SExpr) -> Result<&mut HashTable, &'static str> {
+    mem.hash_table_mut(e).or(Err("type error: expected hash table"))
+}
+
+// Rebuilds the buckets of `e` if objects have moved since they were hashed.
+fn rehash_if_moved(mem: &mut Memory, e: SExpr) -> Result<(), &'static str> {
+    let relocations = mem.relocations();
+    let t = table(mem, e)?;
+    if t.relocations == relocations {
+        return Ok(());
+    }
+    let mut buckets: HashMap<u64, Vec<(SExpr, SExpr)>> = HashMap::new();
+    for (k, v) in t.entries() {
+        buckets.entry(t.hash(mem, k)).or_default().push((k, v));
+    }
+    let t = table_mut(mem, e)?;
+    t.buckets = buckets;
+    t.relocations = relocations;
+    Ok(())
+}
+
+pub fn get(mem: &mut Memory, e: SExpr, key: SExpr) -> Result<Option<SExpr>, &'static str> {
+    rehash_if_moved(mem, e)?;
+    let t = table(mem, e)?;
+    Ok(match t.find(mem, key) {
+        (h, Some(pos)) => Some(t.buckets[&h][pos].1),
+        (_, None) => None,
+    })
+}
+
+pub fn set(mem: &mut Memory, e: SExpr, key: SExpr, value: SExpr) -> Result<(), &'static str> {
+    rehash_if_moved(mem, e)?;
+    let (h, pos) = table(mem, e)?.find(mem, key);
+    let t = table_mut(mem, e)?;
+    let bucket = t.buckets.entry(h).or_default();
+    match pos {
+        Some(pos) => bucket[pos].1 = value,
+        None => {
+            bucket.push((key, value));
+            t.len += 1;
+        }
+    }
+    Ok(())
+}
+
+pub fn delete(mem: &mut Memory, e: SExpr, key: SExpr) -> Result<(), &'static str> {
+    rehash_if_moved(mem, e)?;
+    if let (h, Some(pos)) = table(mem, e)?.find(mem, key) {
+        let t = table_mut(mem, e)?;
+        let bucket = t.buckets.get_mut(&h).unwrap();
+        bucket.swap_remove(pos);
+        if bucket.is_empty() {
+            t.buckets.remove(&h);
+        }
+        t.len -= 1;
+    }
+    Ok(())
+}
+
+fn entries(interp: &Interpreter, e: SExpr) -> Result<Vec<(SExpr, SExpr)>, &'static str> {
+    Ok(table(interp.memory(), e)?.entries())
+}
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("make-hash-table", Arity::Between(0, 1), |interp, args| {
+        let equivalence = match args.first() {
+            None => Equivalence::Equal,
+            Some(&procd) => match interp.memory().native_name(procd) {
+                Some("eq?") | Some("eqv?") => Equivalence::Eqv,
+                Some("equal?") | Some("string=?") => Equivalence::Equal,
+                _ => return Err("hash tables support eq?, eqv?, equal? and string=?"),
+            },
+        };
+        let relocations = interp.memory().relocations();
+        let table = HashTable { relocations, ..HashTable::new(equivalence) };
+        Ok(interp.memory_mut().alloc(Object::HashTable(table)))
+    });
+    interpreter.define_builtin("hash-table?", 1, |interp, args| {
+        Ok(interp.memory().hash_table_ref(args[0]).is_ok())
+    });
+    interpreter.define_builtin("hash-table-ref", Arity::Between(2, 3), |interp, args| {
+        match get(interp.memory_mut(), args[0], args[1])? {
+            Some(value) => Ok(value),
+            None => match args.get(2) {
+                Some(&thunk) => interp.apply(thunk, &[]),
+                None => Err("key not found"),
+            },
+        }
+    });
+    interpreter.define_builtin("hash-table-ref/default", 3, |interp, args| {
+        Ok(get(interp.memory_mut(), args[0], args[1])?.unwrap_or(args[2]))
+    });
+    interpreter.define_builtin("hash-table-set!", 3, |interp, args| {
+        set(interp.memory_mut(), args[0], args[1], args[2])?;
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("hash-table-delete!", 2, |interp, args| {
+        delete(interp.memory_mut(), args[0], args[1])?;
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("hash-table-contains?", 2, |interp, args| {
+        Ok(get(interp.memory_mut(), args[0], args[1])?.is_some())
+    });
+    interpreter.define_builtin("hash-table-update!", Arity::Between(3, 4), |interp, args| {
+        let value = match get(interp.memory_mut(), args[0], args[1])? {
+            Some(value) => value,
+            None => match args.get(3) {
+                Some(&thunk) => interp.apply(thunk, &[])?,
+                None => return Err("key not found"),
+            },
+        };
+        let value = interp.apply(args[2], &[value])?;
+        set(interp.memory_mut(), args[0], args[1], value)?;
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("hash-table-update!/default", 4, |interp, args| {
+        let value = get(interp.memory_mut(), args[0], args[1])?.unwrap_or(args[3]);
+        let value = interp.apply(args[2], &[value])?;
+        set(interp.memory_mut(), args[0], args[1], value)?;
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("hash-table-count", 1, |interp, args| {
+        Ok(table(interp.memory(), args[0])?.len() as i64)
+    });
+    interpreter.define_builtin("hash-table-keys", 1, |interp, args| {
+        let keys = entries(interp, args[0])?.into_iter().map(|(k, _)| k).collect();
+        Ok(interp.memory_mut().list_from_vec(keys))
+    });
+    interpreter.define_builtin("hash-table-values", 1, |interp, args| {
+        let values = entries(interp, args[0])?.into_iter().map(|(_, v)| v).collect();
+        Ok(interp.memory_mut().list_from_vec(values))
+    });
+    interpreter.define_builtin("hash-table->alist", 1, |interp, args| {
+        let mut alist = Vec::new();
+        for (k, v) in entries(interp, args[0])? {
+            alist.push(interp.memory_mut().cons(k, v));
+        }
+        Ok(interp.memory_mut().list_from_vec(alist))
+    });
+    interpreter.define_builtin("hash-table-walk", 2, |interp, args| {
+        for (k, v) in entries(interp, args[0])? {
+            interp.apply(args[1], &[k, v])?;
+        }
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("hash", 1, |interp, args| {
+        Ok((equivalence::hash(interp.memory(), args[0], true) >> 2) as i64)
+    });
+    interpreter.define_builtin("string-hash", 1, |interp, args| {
+        interp.memory().str_ref(args[0]).or(Err("type error: expected string"))?;
+        Ok((equivalence::hash(interp.memory(), args[0], true) >> 2) as i64)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_equal_tables() {
+        assert_eq!(eval("(define h (make-hash-table)) (hash-table-set! h \"a\" 1) (hash-table-ref h \"a\")"), "1");
+        assert_eq!(eval("(define h (make-hash-table equal?)) (hash-table-set! h (list 1 2) 3) \
+                         (hash-table-ref/default h (list 1 2) 0)"), "3");
+        assert_eq!(eval("(define h (make-hash-table string=?)) (hash-table-ref h \"missing\")"), "key not found");
+        assert_eq!(eval("(define h (make-hash-table)) (hash-table-ref h 1 (lambda () 42))"), "42");
+    }
+
+    #[test]
+    fn test_eqv_tables() {
+        assert_eq!(eval("(define h (make-hash-table eqv?)) (hash-table-set! h \"a\" 1) \
+                         (hash-table-ref/default h \"a\" 0)"), "0");
+        assert_eq!(eval("(define h (make-hash-table eq?)) (define k (list 1)) (hash-table-set! h k 1) \
+                         (hash-table-set! h 2.5 2) (list (hash-table-ref h k) (hash-table-ref h 2.5))"), "(1 2)");
+        assert_eq!(eval("(make-hash-table +)"), "hash tables support eq?, eqv?, equal? and string=?");
+    }
+
+    #[test]
+    fn test_update_delete() {
+        assert_eq!(eval("(define h (make-hash-table)) \
+                         (hash-table-update!/default h (quote a) (lambda (x) (+ x 1)) 0) \
+                         (hash-table-update!/default h (quote a) (lambda (x) (+ x 1)) 0) \
+                         (hash-table-ref h (quote a))"), "2");
+        assert_eq!(eval("(define h (make-hash-table)) (hash-table-update! h 1 (lambda (x) x))"), "key not found");
+        assert_eq!(eval("(define h (make-hash-table)) (hash-table-set! h 1 1) (hash-table-set! h 2 2) \
+                         (hash-table-delete! h 1) (hash-table-delete! h 3) \
+                         (list (hash-table-count h) (hash-table-contains? h 1) (hash-table-keys h))"), "(1 false (2))");
+    }
+
+    #[test]
+    fn test_walk() {
+        assert_eq!(eval("(define h (make-hash-table)) (hash-table-set! h 1 10) (hash-table-set! h 2 20) \
+                         (define sum 0) (hash-table-walk h (lambda (k v) (set! sum (+ sum k v)))) sum"), "33");
+        assert_eq!(eval("(define h (make-hash-table)) (hash-table-set! h 1 10) (hash-table->alist h)"), "((1 . 10))");
+        assert_eq!(eval("(= (string-hash \"abc\") (string-hash (string-append \"a\" \"bc\")))"), "true");
+        assert_eq!(eval("(string-hash 1)"), "type error: expected string");
+    }
+
+    #[test]
+    fn test_collection() {
+        let mut interpreter = Interpreter::new(2000);
+        interpreter.eval_string("
+            (define keys (list (list 1) (expt 10 30) (list 2) \"s\"))
+            (define tables (map make-hash-table (list eq? eqv? equal? string=?)))
+            (for-each (lambda (t k) (hash-table-set! t k 1)) tables keys)").unwrap();
+        let relocations = interpreter.memory().relocations();
+        interpreter.eval_string("(map (lambda (i) (list i i)) (iota 100))").unwrap();
+        interpreter.collect_garbage();
+        assert!(interpreter.memory().relocations() > relocations);
+        let res = interpreter.eval_string("
+            (map (lambda (t k) (hash-table-ref/default t k 0)) tables
+                 (list (car keys) (expt 10 30) (list 2) (string #\\s)))").unwrap();
+        assert_eq!(interpreter.to_string(res), "(1 1 1 1)");
+        // the tables were rehashed under the current relocation count
+        let tables = interpreter.lookup("tables").unwrap();
+        let mem = interpreter.memory();
+        for t in mem.vec_from_list(tables).unwrap() {
+            assert_eq!(table(mem, t).unwrap().relocations, mem.relocations());
+        }
+    }
+}
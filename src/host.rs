@@ -0,0 +1,194 @@
+use std::any::Any;
+use std::fmt;
+
+use crate::sexpr::{SExpr, Memory, Object};
+use crate::convert::ToSExpr;
+
+type EqualsHook = Box<dyn Fn(&dyn Any, &dyn Any) -> bool>;
+type DisplayHook = Box<dyn Fn(&dyn Any) -> String>;
+type Finalizer = Box<dyn FnOnce(Box<dyn Any>)>;
+
+/// A Rust value stored in the Scheme heap. Scheme code can pass it around
+/// but not look inside; native procedures get it back with
+/// `Interpreter::host_ref` and `Interpreter::host_mut`.
+///
+/// ```
+/// use rscheme::{Interpreter, HostObject};
+///
+/// struct Counter(i64);
+///
+/// let mut interpreter = Interpreter::default();
+/// interpreter.define_native("make-counter", 0, |_, _| {
+///     Ok(HostObject::new("counter", Counter(0))
+///         .with_display(|c: &Counter| format!("#<counter {}>", c.0)))
+/// }).unwrap();
+/// interpreter.define_native("tick!", 1, |interp, args| {
+///     let counter = interp.host_mut::<Counter>(args[0])?;
+///     counter.0 += 1;
+///     Ok(counter.0)
+/// }).unwrap();
+/// let c = interpreter.eval_string("(define c (make-counter)) (tick! c) (tick! c) c").unwrap();
+/// assert_eq!(interpreter.to_string(c), "#<counter 2>");
+/// ```
+pub struct HostObject {
+    type_name: &'static str,
+    value: Option<Box<dyn Any>>,
+    equals: Option<EqualsHook>,
+    display: Option<DisplayHook>,
+    finalizer: Option<Finalizer>,
+}
+
+impl HostObject {
+    pub fn new<T: Any>(type_name: &'static str, value: T) -> Self {
+        HostObject {
+            type_name,
+            value: Some(Box::new(value)),
+            equals: None,
+            display: None,
+            finalizer: None,
+        }
+    }
+
+    /// Compares host objects holding a `T` with `equals` instead of by identity.
+    pub fn with_equality<T: Any>(mut self, equals: impl Fn(&T, &T) -> bool + 'static) -> Self {
+        self.equals = Some(Box::new(move |a, b| match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+            (Some(a), Some(b)) => equals(a, b),
+            _ => false,
+        }));
+        self
+    }
+
+    /// Prints the object with `display` instead of as `#<type_name>`.
+    pub fn with_display<T: Any>(mut self, display: impl Fn(&T) -> String + 'static) -> Self {
+        self.display = Some(Box::new(move |a| match a.downcast_ref::<T>() {
+            Some(a) => display(a),
+            None => String::new(),
+        }));
+        self
+    }
+
+    /// Runs `finalizer` on the value when the object is freed, either by
+    /// garbage collection or when the interpreter is dropped.
+    pub fn with_finalizer<T: Any>(mut self, finalizer: impl FnOnce(T) + 'static) -> Self {
+        self.finalizer = Some(Box::new(move |value| {
+            if let Ok(value) = value.downcast::<T>() {
+                finalizer(*value);
+            }
+        }));
+        self
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    fn value(&self) -> &dyn Any {
+        self.value.as_deref().unwrap()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.value.as_deref_mut().unwrap().downcast_mut()
+    }
+
+    /// Compares with the equality hook, falling back to identity.
+    pub fn equals(&self, other: &HostObject) -> bool {
+        if std::ptr::eq(self, other) {
+            return true;
+        }
+        match self.equals {
+            Some(ref equals) if self.type_name == other.type_name => equals(self.value(), other.value()),
+            _ => false,
+        }
+    }
+}
+
+impl PartialEq for HostObject {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+impl fmt::Display for HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.display {
+            Some(ref display) => write!(f, "{}", display(self.value())),
+            None => write!(f, "#<{}>", self.type_name),
+        }
+    }
+}
+
+impl fmt::Debug for HostObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HostObject({})", self.type_name)
+    }
+}
+
+impl Drop for HostObject {
+    fn drop(&mut self) {
+        if let (Some(finalizer), Some(value)) = (self.finalizer.take(), self.value.take()) {
+            finalizer(value);
+        }
+    }
+}
+
+impl ToSExpr for HostObject {
+    fn to_sexpr(self, mem: &mut Memory) -> SExpr {
+        mem.alloc(Object::Host(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[derive(PartialEq, Debug)]
+    struct Point(i64, i64);
+
+    fn point(x: i64, y: i64) -> HostObject {
+        HostObject::new("point", Point(x, y))
+            .with_equality(|a: &Point, b: &Point| a == b)
+            .with_display(|p: &Point| format!("#<point {} {}>", p.0, p.1))
+    }
+
+    #[test]
+    fn test_hooks() {
+        assert_eq!(point(1, 2), point(1, 2));
+        assert_ne!(point(1, 2), point(2, 1));
+        assert_eq!(point(1, 2).to_string(), "#<point 1 2>");
+
+        let plain = HostObject::new("plain", Point(1, 2));
+        assert_ne!(plain, HostObject::new("plain", Point(1, 2)));
+        assert!(plain.equals(&plain));
+        assert_eq!(plain.to_string(), "#<plain>");
+        assert_eq!(plain.downcast_ref::<Point>(), Some(&Point(1, 2)));
+        assert_eq!(plain.downcast_ref::<i64>(), None);
+    }
+
+    #[test]
+    fn test_finalizer() {
+        let freed = Rc::new(Cell::new(0));
+        let f = freed.clone();
+        let mut mem = Memory::new(10);
+        let host = HostObject::new("point", Point(3, 4))
+            .with_finalizer(move |p: Point| f.set(p.0 + p.1));
+        let e = host.to_sexpr(&mut mem);
+        assert_eq!(freed.get(), 0);
+        if let SExpr::Ref(addr) = e {
+            mem.free(addr);
+        }
+        assert_eq!(freed.get(), 7);
+
+        let f = freed.clone();
+        HostObject::new("point", Point(1, 1))
+            .with_finalizer(move |p: Point| f.set(p.0 + p.1))
+            .to_sexpr(&mut mem);
+        drop(mem);
+        assert_eq!(freed.get(), 2);
+    }
+}
@@ -1,30 +1,121 @@
-use crate::sexpr::{SExpr, Memory, Object, Environment};
+use crate::sexpr::{SExpr, Memory, Object, Environment, OUT_OF_MEMORY};
 use crate::scanner::Scanner;
 use crate::parser::Parser;
+use crate::expander;
+use crate::strings;
+use crate::chars;
+use crate::vectors;
+use crate::bytevectors;
+use crate::predicates::{self, is_list};
+use crate::lists;
+use crate::evaluation;
+use crate::equivalence;
+use crate::hashtables;
+use crate::promises::{self, Promise};
+use crate::streams;
+use crate::gc;
+use crate::records::{self, RecordType, RecordProcedure};
+use crate::numbers::{self, Number};
+use crate::convert::{ToSExpr, FromSExpr};
+use crate::native::{Native, Arity, FromArgs};
+use crate::symbol::{self, Symbol};
+use std::any::Any;
+use std::cmp::Ordering;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
 
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Primitive {
     Add, Sub, Mul, Div, 
     Eql, Gt, Gte, Lt, Lte,
     Car, Cdr, Cons, SetCar, SetCdr,
-    NullQ, List
+    NullQ, List, Apply
 }
 
-pub struct Interpreter<'s> {
-    mem: Memory<'s>,
-    initial_env: SExpr<'s>,
+// What is left of an expression after one step of evaluation.
+enum Step {
+    Done(SExpr),
+    // evaluate this expression in this environment
+    Tail(SExpr, SExpr),
 }
 
-impl<'s> Interpreter<'s> {
+pub const SPECIAL_FORMS: &[&str] = &[
+    "quote", "set!", "define", "if", "begin", "lambda", "cond", "let", "define-record-type",
+    "the-environment", "delay", "delay-force", "cons-stream", "stream-cons", "define-stream",
+];
+
+/// Number of objects in the memory of an `Interpreter::default()`.
+pub const DEFAULT_MEMORY_SIZE: usize = 1 << 18;
+
+/// Keeps a value alive across `Interpreter::collect_garbage`, see
+/// `Interpreter::protect`.
+#[derive(Debug)]
+pub struct Handle(usize);
+
+pub struct Interpreter {
+    mem: Memory,
+    initial_env: SExpr,
+    // the standard bindings, as they were before any user definitions
+    report_env: SExpr,
+    // values held by the evaluation in progress, which garbage collection
+    // must keep
+    roots: Vec<SExpr>,
+    // the epoch the outermost evaluation in progress started in, or 0
+    // between evaluations; collections during it keep everything born
+    // before, see `isolated`
+    young_since: u64,
+    // values kept for Rust code, see `protect`
+    protected: Vec<Option<SExpr>>,
+    // the allocation count before which no collection is attempted
+    next_collection: usize,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new(DEFAULT_MEMORY_SIZE)
+    }
+}
+
+impl Interpreter {
+    /// Creates an interpreter whose memory holds `memsize` objects. Panics if
+    /// the standard procedures do not fit.
     pub fn new(memsize: usize) -> Self {
-        let mut obj = Interpreter { mem: Memory::new(memsize), initial_env: SExpr::Nil };
-        obj.setup_intial_env();
+        let mut obj = Interpreter {
+            mem: Memory::new(memsize),
+            initial_env: SExpr::Nil,
+            report_env: SExpr::Nil,
+            roots: Vec::new(),
+            young_since: 0,
+            protected: Vec::new(),
+            next_collection: 0,
+        };
+        let res = obj.isolated(|interp| {
+            interp.setup_intial_env();
+            numbers::define_procedures(interp);
+            strings::define_procedures(interp);
+            chars::define_procedures(interp);
+            vectors::define_procedures(interp);
+            bytevectors::define_procedures(interp);
+            predicates::define_procedures(interp);
+            lists::define_procedures(interp);
+            evaluation::define_procedures(interp);
+            equivalence::define_procedures(interp);
+            hashtables::define_procedures(interp);
+            promises::define_procedures(interp);
+            streams::define_procedures(interp);
+            interp.report_env = interp.copy_env(interp.initial_env);
+            Ok(())
+        });
+        if let Err(err) = res {
+            panic!("{}", err);
+        }
+        obj.collect_garbage();
         obj
     }
 
     pub fn setup_intial_env(&mut self) {
         let mut env = Environment::new(SExpr::Nil);
-        let mut p = |a, b| env.insert(a, self.mem.alloc(Object::PrimitiveProcedure(b)));
+        let mut p = |a, b| env.insert(self.mem.intern(a), self.mem.alloc(Object::PrimitiveProcedure(b)));
         p("+", Primitive::Add);
         p("-", Primitive::Sub);
         p("*", Primitive::Mul);
@@ -41,164 +132,599 @@ impl<'s> Interpreter<'s> {
         p("set-cdr!", Primitive::SetCdr);
         p("null?", Primitive::NullQ);
         p("list", Primitive::List);
+        p("apply", Primitive::Apply);
         self.initial_env = self.mem.alloc(Object::Env(env));
     }
 
-    pub fn eval_string(&mut self, s: &'s str) -> Result<SExpr<'s>, &'static str> {
+    /// Parses every expression in `s` without evaluating them.
+    pub fn read(&mut self, s: &str) -> Result<Vec<SExpr>, &'static str> {
         let scanner = Scanner::new(s);
-        let tokens = scanner.scan_tokens().expect("scan err");
-        let parser = Parser::new(tokens, &mut self.mem);
-        let expr = parser.parse().expect("parse err");
-        self.eval(expr)
+        let tokens = scanner.scan_tokens().map_err(|e| e.message)?;
+        self.isolated(|interp| {
+            let parser = Parser::new(tokens, &mut interp.mem);
+            parser.parse_all().map_err(|e| e.message)
+        })
+    }
+
+    /// Evaluates every expression in `s`, returning the value of the last.
+    /// Collections it runs on its own only free objects it allocated, so
+    /// values returned by earlier evaluations stay valid; what those left
+    /// behind is freed by `collect_garbage`.
+    pub fn eval_string(&mut self, s: &str) -> Result<SExpr, &'static str> {
+        let forms = self.read(s)?;
+        self.eval_all(&forms)
+    }
+
+    /// Evaluates `forms` in order, returning the value of the last. Unlike
+    /// calling `eval` on each, this keeps the forms not yet evaluated from
+    /// being garbage collected.
+    pub fn eval_all(&mut self, forms: &[SExpr]) -> Result<SExpr, &'static str> {
+        self.isolated(|interp| {
+            let base = interp.roots.len();
+            interp.roots.extend(forms);
+            let mut res = Err("Empty expression");
+            for &form in forms {
+                res = expander::expand(&mut interp.mem, form).and_then(|form| interp._eval(form, interp.initial_env));
+                if res.is_err() {
+                    break;
+                }
+            }
+            interp.roots.truncate(base);
+            res
+        })
+    }
+
+    /// Prints `e` the way the REPL does.
+    pub fn to_string(&self, e: SExpr) -> String {
+        self.mem.to_string(e)
+    }
+
+    /// Rewrites the derived forms in `e` into core forms.
+    pub fn expand(&mut self, e: SExpr) -> Result<SExpr, &'static str> {
+        self.isolated(|interp| expander::expand(&mut interp.mem, e))
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.mem
+    }
+
+    pub(crate) fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.mem
+    }
+
+    /// Converts a Rust value into a Scheme value, allocating if needed.
+    pub fn to_sexpr<T: ToSExpr>(&mut self, value: T) -> Result<SExpr, &'static str> {
+        self.isolated(|interp| Ok(value.to_sexpr(&mut interp.mem)))
+    }
+
+    /// Converts a Scheme value produced by this interpreter into a Rust value.
+    pub fn from_sexpr<T: FromSExpr>(&self, e: SExpr) -> Result<T, &'static str> {
+        T::from_sexpr(e, &self.mem)
     }
 
-    pub fn eval(&mut self, e: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
-        self._eval(e, self.initial_env)
+    /// Binds `name` in the global environment.
+    pub fn define<T: ToSExpr>(&mut self, name: &str, value: T) -> Result<(), &'static str> {
+        let value = self.to_sexpr(value)?;
+        let name = self.mem.intern(name);
+        self.mem.env_insert(self.initial_env, name, value)
     }
 
-    fn _eval(&mut self, e: SExpr<'s>, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    /// Binds `name` in the global environment to a procedure implemented by
+    /// `func`. The number of arguments is checked against `arity` before
+    /// `func` is called, and its result is converted with `ToSExpr`. Values
+    /// it captures must be protected to survive `collect_garbage`.
+    ///
+    /// ```
+    /// use rscheme::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::default();
+    /// interpreter.define_native("hypot", 2, |interp, args| {
+    ///     let (x, y): (f64, f64) = interp.args(args)?;
+    ///     Ok(x.hypot(y))
+    /// }).unwrap();
+    /// let res = interpreter.eval_string("(hypot 3 4)").unwrap();
+    /// assert_eq!(interpreter.from_sexpr::<f64>(res), Ok(5.0));
+    /// ```
+    pub fn define_native<A, F, R>(&mut self, name: &str, arity: A, func: F) -> Result<(), &'static str>
+    where
+        A: Into<Arity>,
+        F: Fn(&mut Interpreter, &[SExpr]) -> Result<R, &'static str> + 'static,
+        R: ToSExpr,
+    {
+        self.isolated(|interp| {
+            interp.define_builtin(name, arity, func);
+            Ok(())
+        })
+    }
+
+    // Like `define_native`, for the standard procedures, which are defined
+    // while the interpreter is created.
+    pub(crate) fn define_builtin<A, F, R>(&mut self, name: &str, arity: A, func: F)
+    where
+        A: Into<Arity>,
+        F: Fn(&mut Interpreter, &[SExpr]) -> Result<R, &'static str> + 'static,
+        R: ToSExpr,
+    {
+        let func = move |interp: &mut Interpreter, args: &[SExpr]| {
+            let res = func(interp, args)?;
+            Ok(res.to_sexpr(&mut interp.mem))
+        };
+        let native = Native::new(name, arity.into(), Rc::new(func));
+        let procd = self.mem.alloc(Object::NativeProcedure(native));
+        let name = self.mem.intern(name);
+        // the global environment is always an environment
+        self.mem.env_insert(self.initial_env, name, procd).expect("global environment");
+    }
+
+    /// Borrows the Rust value inside the host object `e`.
+    pub fn host_ref<T: Any>(&self, e: SExpr) -> Result<&T, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem.get(addr) {
+                Object::Host(host) => host.downcast_ref().ok_or("type error: wrong host object type"),
+                _ => Err("type error: expected host object"),
+            }
+            _ => Err("type error: expected host object"),
+        }
+    }
+
+    /// Mutably borrows the Rust value inside the host object `e`.
+    pub fn host_mut<T: Any>(&mut self, e: SExpr) -> Result<&mut T, &'static str> {
         match e {
+            SExpr::Ref(addr) => match self.mem.get_mut(addr) {
+                Object::Host(host) => host.downcast_mut().ok_or("type error: wrong host object type"),
+                _ => Err("type error: expected host object"),
+            }
+            _ => Err("type error: expected host object"),
+        }
+    }
+
+    /// Converts the arguments of a native procedure, checking their number.
+    pub fn args<T: FromArgs>(&self, args: &[SExpr]) -> Result<T, &'static str> {
+        T::from_args(args, &self.mem)
+    }
+
+    /// Names bound in the global environment, including the primitives.
+    pub fn bound_names(&self) -> Vec<&str> {
+        let names = self.mem.env_names(self.initial_env).unwrap_or_default();
+        names.into_iter().map(|sym| self.mem.symbol_name(sym)).collect()
+    }
+
+    /// Interns `name`, returning the symbol used for it in this interpreter.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        self.mem.intern(name)
+    }
+
+    pub fn symbol_name(&self, sym: Symbol) -> &str {
+        self.mem.symbol_name(sym)
+    }
+
+    pub fn eval(&mut self, e: SExpr) -> Result<SExpr, &'static str> {
+        self.isolated(|interp| {
+            let e = expander::expand(&mut interp.mem, e)?;
+            interp._eval(e, interp.initial_env)
+        })
+    }
+
+    /// Evaluates `e` in the environment `env` instead of the global one.
+    pub fn eval_in(&mut self, e: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
+        self.isolated(|interp| {
+            let e = expander::expand(&mut interp.mem, e)?;
+            interp.eval_in_rooted(e, env)
+        })
+    }
+
+    // Like `eval_in` for an expression that is already expanded, but garbage
+    // may be collected, so everything else the caller still needs must be
+    // rooted.
+    pub(crate) fn eval_in_rooted(&mut self, e: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
+        match self.mem.deref(env) {
+            Some(Object::Env(_)) => self._eval(e, env),
+            _ => Err("type error: expected environment"),
+        }
+    }
+
+    /// The global environment, where `eval_string` defines things.
+    pub fn interaction_environment(&self) -> SExpr {
+        self.initial_env
+    }
+
+    /// A fresh environment holding only the standard bindings.
+    pub fn report_environment(&mut self) -> Result<SExpr, &'static str> {
+        self.isolated(|interp| Ok(interp.copy_env(interp.report_env)))
+    }
+
+    fn copy_env(&mut self, env: SExpr) -> SExpr {
+        let bindings = match self.mem.deref(env) {
+            Some(Object::Env(env)) => env.clone(),
+            _ => Environment::new(SExpr::Nil),
+        };
+        self.mem.alloc(Object::Env(bindings))
+    }
+
+    fn _eval(&mut self, e: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
+        let base = self.roots.len();
+        let res = self.eval_frame(e, env);
+        self.roots.truncate(base);
+        res
+    }
+
+    // Expressions in tail position replace `e` and `env` instead of being
+    // evaluated recursively, so loops written as tail calls run in constant
+    // space. Between steps only `e` and `env` are kept, on top of the roots.
+    fn eval_frame(&mut self, mut e: SExpr, mut env: SExpr) -> Result<SExpr, &'static str> {
+        let base = self.roots.len();
+        loop {
+            self.roots.truncate(base);
+            self.roots.extend([e, env]);
+            self.collect_if_low();
+            match self.eval_step(e, env)? {
+                Step::Done(res) => return Ok(res),
+                Step::Tail(next, next_env) => {
+                    e = next;
+                    env = next_env;
+                }
+            }
+        }
+    }
+
+    fn eval_step(&mut self, e: SExpr, env: SExpr) -> Result<Step, &'static str> {
+        let res = match e {
             // values
-            v @ SExpr::Int(_) => Ok(v),
-            v @ SExpr::Float(_) => Ok(v),
-            v @ SExpr::Bool(_) => Ok(v),
-            v @ SExpr::Str(_) => Ok(v),
-            v @ SExpr::Nil => Ok(v),
+            v @ SExpr::Int(_) => v,
+            v @ SExpr::Float(_) => v,
+            v @ SExpr::Bool(_) => v,
+            v @ SExpr::Char(_) => v,
+            v @ SExpr::Nil => v,
+            v @ SExpr::Eof => v,
+            v @ SExpr::Unspecified => v,
             // variable
-            SExpr::Sym(s) => self.eval_var(s, env),
+            SExpr::Sym(s) => self.eval_var(s, env)?,
             SExpr::Ref(addr) => match self.mem.get(addr) {
                 // objects
-                Object::PrimitiveProcedure(_) => Ok(SExpr::Ref(addr)),
-                Object::CompoundProcedure(_) => Ok(SExpr::Ref(addr)),
-                Object::Env(_) => Ok(SExpr::Ref(addr)),
+                Object::PrimitiveProcedure(_) => SExpr::Ref(addr),
+                Object::CompoundProcedure(_) => SExpr::Ref(addr),
+                Object::NativeProcedure(_) => SExpr::Ref(addr),
+                Object::Env(_) => SExpr::Ref(addr),
+                Object::Str(_) => SExpr::Ref(addr),
+                Object::Vector(_) => SExpr::Ref(addr),
+                Object::BigInt(_) => SExpr::Ref(addr),
+                Object::Rational(_) => SExpr::Ref(addr),
+                Object::Bytevector(_) => SExpr::Ref(addr),
+                Object::HashTable(_) => SExpr::Ref(addr),
+                Object::RecordType(_) => SExpr::Ref(addr),
+                Object::Record(_) => SExpr::Ref(addr),
+                Object::RecordProcedure(_) => SExpr::Ref(addr),
+                Object::Host(_) => SExpr::Ref(addr),
+                Object::Promise(_) => SExpr::Ref(addr),
                 // special forms
-                &Object::Pair(SExpr::Sym("quote"), e) => self.eval_quote(e),
-                &Object::Pair(SExpr::Sym("set!"), e) => self.eval_set(e, env),
-                &Object::Pair(SExpr::Sym("define"), e) => self.eval_define(e, env),
-                &Object::Pair(SExpr::Sym("if"), e) => self.eval_if(e, env),
-                &Object::Pair(SExpr::Sym("begin"), e) => {
-                    let seq = self.eval_sequence(e, env)?;
-                    Ok(seq[seq.len()-1])
+                &Object::Pair(SExpr::Sym(symbol::QUOTE), e) => self.eval_quote(e)?,
+                &Object::Pair(SExpr::Sym(symbol::SET), e) => self.eval_set(e, env)?,
+                &Object::Pair(SExpr::Sym(symbol::DEFINE), e) => self.eval_define(e, env)?,
+                &Object::Pair(SExpr::Sym(symbol::IF), e) => match self.eval_if(e, env)? {
+                    Some(branch) => return Ok(Step::Tail(branch, env)),
+                    None => SExpr::Unspecified,
                 }
-                &Object::Pair(SExpr::Sym("lambda"), e) => self.eval_lambda(e, env),
-                Object::Pair(SExpr::Sym("cond"), _) => unimplemented!(),
+                &Object::Pair(SExpr::Sym(symbol::BEGIN), e) => match self.eval_body(e, env)? {
+                    Some(last) => return Ok(Step::Tail(last, env)),
+                    None => SExpr::Nil,
+                }
+                &Object::Pair(SExpr::Sym(symbol::LAMBDA), e) => self.eval_lambda(e, env)?,
+                &Object::Pair(SExpr::Sym(symbol::DEFINE_RECORD_TYPE), e) => self.eval_define_record_type(e, env)?,
+                Object::Pair(SExpr::Sym(symbol::THE_ENVIRONMENT), SExpr::Nil) => env,
+                Object::Pair(SExpr::Sym(symbol::THE_ENVIRONMENT), _) => return Err("ill formed the-environment"),
+                &Object::Pair(SExpr::Sym(symbol::DELAY), e) => self.eval_delay(e, env, false, "ill formed delay")?,
+                &Object::Pair(SExpr::Sym(symbol::DELAY_FORCE), e) => self.eval_delay(e, env, true, "ill formed delay-force")?,
+                &Object::Pair(SExpr::Sym(symbol::CONS_STREAM), e) => self.eval_cons_stream(e, env)?,
+                &Object::Pair(SExpr::Sym(symbol::STREAM_CONS), e) => self.eval_stream_cons(e, env)?,
                 // application
-                &Object::Pair(operator, operands) => self.eval_application(operator, operands, env),
+                &Object::Pair(operator, operands) => return self.eval_application(operator, operands, env),
                 // should not occur
-                Object::Empty(_) => Err("dereferencing empty location"),
+                Object::Empty(_) => return Err("dereferencing empty location"),
             }
-        }
+        };
+        Ok(Step::Done(res))
     }
 
-    fn eval_var(&self, k: &'s str, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    fn eval_var(&self, k: Symbol, env: SExpr) -> Result<SExpr, &'static str> {
         match self.mem.env_get(k, env) {
             Ok(e) => Ok(e),
             Err(_) => Err("Unbound variable"),
         }
     }
 
-    fn eval_application(&mut self, operator: SExpr<'s>, operands: SExpr<'s>, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
-        match self._eval(operator, env)? {
-            SExpr::Ref(addr) => match self.mem.get(addr) {
-                &Object::PrimitiveProcedure(procd) => {
-                    let ops = self.eval_sequence(operands, env)?;
-                    self.eval_primitive(procd, ops)
+    // The procedure and its arguments stay rooted until the call is done or
+    // continues in tail position.
+    fn eval_application(&mut self, operator: SExpr, operands: SExpr, env: SExpr) -> Result<Step, &'static str> {
+        let procd = self._eval(operator, env)?;
+        self.roots.push(procd);
+        if !self.is_procedure(procd) {
+            return Err("Applying non procedure");
+        }
+        let ops = self.eval_sequence(operands, env)?;
+        self.apply_step(procd, ops)
+    }
+
+    // Runs `f` as an evaluation of its own, unless one is already in
+    // progress. Collections during it only free objects it allocates, so
+    // values Rust code got from earlier evaluations are kept. Calls back into
+    // the interpreter from a native procedure are part of the evaluation
+    // that called it, so what one of them leaves behind can be freed by the
+    // next; the native must root what it keeps across them. Running out of
+    // memory abandons the whole evaluation and is reported as an error.
+    fn isolated<T>(&mut self, f: impl FnOnce(&mut Interpreter) -> Result<T, &'static str>)
+        -> Result<T, &'static str> {
+        if self.young_since != 0 {
+            return f(self);
+        }
+        let since = self.mem.next_epoch();
+        self.young_since = since;
+        let res = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        self.young_since = 0;
+        match res {
+            Ok(res) => res,
+            Err(payload) => {
+                // free what the abandoned evaluation allocated
+                self.roots.clear();
+                self.next_collection = 0;
+                self.collect(since);
+                match payload.downcast_ref::<&str>() {
+                    Some(&msg) if msg == OUT_OF_MEMORY => Err(OUT_OF_MEMORY),
+                    _ => panic::resume_unwind(payload),
                 }
-                &Object::CompoundProcedure(procd) => {
-                    let ops = self.eval_sequence(operands, env)?;
-                    self.eval_compound(procd, ops)
-                },
-                _ => Err("Applying non procedure"),
+            }
+        }
+    }
+
+    // Collects garbage if memory is running low. Only called between
+    // evaluation steps, where everything live is reachable from the roots.
+    fn collect_if_low(&mut self) {
+        if self.mem.available() > self.mem.capacity() / 8
+            || self.mem.allocations() < self.next_collection {
+            return;
+        }
+        self.collect(self.young_since);
+        // when most of memory is live, wait for some allocations before
+        // trying again
+        self.next_collection = self.mem.allocations() + self.mem.available() / 2;
+    }
+
+    /// Frees every object that is unreachable from the global environment,
+    /// the evaluation in progress and protected values, returning how many
+    /// were freed. Evaluation collects on its own when memory runs low, but
+    /// then only frees what that evaluation allocated; this also frees
+    /// values only Rust code holds, such as earlier results of `eval_string`
+    /// or values captured by native procedures, unless they are protected.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.collect(0)
+    }
+
+    fn collect(&mut self, since: u64) -> usize {
+        let mut roots = vec![self.initial_env, self.report_env];
+        roots.extend(&self.roots);
+        roots.extend(self.protected.iter().flatten());
+        gc::collect(&mut self.mem, &roots, since)
+    }
+
+    /// Keeps `e` from being freed by `collect_garbage` until `handle` is
+    /// released.
+    ///
+    /// ```
+    /// use rscheme::Interpreter;
+    ///
+    /// let mut interpreter = Interpreter::default();
+    /// let list = interpreter.eval_string("(list 1 2 3)").unwrap();
+    /// let handle = interpreter.protect(list);
+    /// interpreter.collect_garbage();
+    /// assert_eq!(interpreter.to_string(list), "(1 2 3)");
+    /// interpreter.release(handle);
+    /// ```
+    pub fn protect(&mut self, e: SExpr) -> Handle {
+        match self.protected.iter().position(Option::is_none) {
+            Some(slot) => {
+                self.protected[slot] = Some(e);
+                Handle(slot)
+            }
+            None => {
+                self.protected.push(Some(e));
+                Handle(self.protected.len() - 1)
+            }
+        }
+    }
+
+    /// Lets the value `handle` protects be freed again.
+    pub fn release(&mut self, handle: Handle) {
+        if let Some(slot) = self.protected.get_mut(handle.0) {
+            *slot = None;
+        }
+    }
+
+    /// Whether `e` can be passed to `apply`.
+    pub fn is_procedure(&self, e: SExpr) -> bool {
+        match e {
+            SExpr::Ref(addr) => matches!(self.mem.get(addr),
+                Object::PrimitiveProcedure(_) | Object::CompoundProcedure(_) | Object::NativeProcedure(_)
+                | Object::RecordProcedure(_)),
+            _ => false,
+        }
+    }
+
+    /// Calls the procedure `procd` with already evaluated `args`.
+    ///
+    /// ```
+    /// use rscheme::{Interpreter, SExpr};
+    ///
+    /// let mut interpreter = Interpreter::default();
+    /// interpreter.eval_string("(define (on-event e) (* e 10))").unwrap();
+    /// let handler = interpreter.lookup("on-event").unwrap();
+    /// let mut results = Vec::new();
+    /// for event in 1..4 {
+    ///     let res = interpreter.apply(handler, &[SExpr::Int(event)]).unwrap();
+    ///     results.push(interpreter.from_sexpr::<i64>(res).unwrap());
+    /// }
+    /// assert_eq!(results, vec![10, 20, 30]);
+    /// ```
+    ///
+    /// Like `eval_string`, it only collects garbage it allocated itself.
+    /// Called from a native procedure, it may also free what the native
+    /// allocated: the native's arguments are kept, but anything else it
+    /// holds across the call must be protected.
+    pub fn apply(&mut self, procd: SExpr, args: &[SExpr]) -> Result<SExpr, &'static str> {
+        self.isolated(|interp| interp.call(procd, args))
+    }
+
+    // Keeps `e` from being collected until `unroot` or the return of the
+    // native procedure being called, returning the slot `set_root` replaces
+    // it in.
+    pub(crate) fn root(&mut self, e: SExpr) -> usize {
+        self.roots.push(e);
+        self.roots.len() - 1
+    }
+
+    pub(crate) fn set_root(&mut self, slot: usize, e: SExpr) {
+        self.roots[slot] = e;
+    }
+
+    // Drops the root in `slot` and every one rooted after it.
+    pub(crate) fn unroot(&mut self, slot: usize) {
+        self.roots.truncate(slot);
+    }
+
+    fn call(&mut self, procd: SExpr, args: &[SExpr]) -> Result<SExpr, &'static str> {
+        let base = self.roots.len();
+        self.roots.push(procd);
+        self.roots.extend(args);
+        let res = match self.apply_step(procd, args.to_vec()) {
+            Ok(Step::Done(res)) => Ok(res),
+            Ok(Step::Tail(e, env)) => self.eval_frame(e, env),
+            Err(err) => Err(err),
+        };
+        self.roots.truncate(base);
+        res
+    }
+
+    // Calls `procd` with `args`, which must be rooted. Compound procedures
+    // continue with the last expression of their body, and `apply` with
+    // the procedure it calls.
+    fn apply_step(&mut self, procd: SExpr, args: Vec<SExpr>) -> Result<Step, &'static str> {
+        let obj = match procd {
+            SExpr::Ref(addr) => self.mem.get(addr),
+            _ => return Err("Applying non procedure"),
+        };
+        match *obj {
+            Object::PrimitiveProcedure(Primitive::Apply) => {
+                let (procd, args) = self.spread_arguments(&args)?;
+                self.apply_step(procd, args)
+            }
+            Object::PrimitiveProcedure(procd) => self.eval_primitive(procd, args).map(Step::Done),
+            Object::CompoundProcedure(procd) => {
+                let (body, env) = self.bind_arguments(procd, args)?;
+                self.roots.push(env);
+                let last = self.eval_body(body, env)?.ok_or("empty procedure body")?;
+                Ok(Step::Tail(last, env))
+            }
+            Object::NativeProcedure(ref native) => {
+                let native = native.clone();
+                native.call(self, &args).map(Step::Done)
+            }
+            Object::RecordProcedure(ref procd) => {
+                let procd = procd.clone();
+                records::apply(&mut self.mem, &procd, &args).map(Step::Done)
             }
             _ => Err("Applying non procedure"),
         }
     }
 
-    fn eval_sequence(&mut self, operands: SExpr<'s>, env: SExpr<'s>) -> Result<Vec<SExpr<'s>>, &'static str> {
-        let vec_op = self.mem.vec_from_list(operands).or(Err("Ill formed list"))?;
-        let vec_op_evalr: Result<Vec<SExpr<'s>>, &'static str> = vec_op.iter().map(|&e| self._eval(e, env)).collect();
-        vec_op_evalr
-        // self.mem.list_from_vec(vec_op_evalr?).or(Ok(SExpr::Nil))
-    }    
-
-    fn eval_primitive(&mut self, procd: Primitive, operands: Vec<SExpr<'s>>) -> Result<SExpr<'s>, &'static str> {
-        macro_rules! arithmetic_fold { 
-            ( $op_iter:expr, $initial:expr, $op:tt) => {
-                $op_iter.try_fold($initial, |acc, &e| match (acc, e) {
-                    (SExpr::Int(acc), SExpr::Int(x)) => Ok(SExpr::Int(acc $op x)),
-                    (SExpr::Int(acc), SExpr::Float(x)) => Ok(SExpr::Float(acc as f64 $op x)),
-                    (SExpr::Float(acc), SExpr::Int(x)) => Ok(SExpr::Float(acc $op x as f64)),
-                    (SExpr::Float(acc), SExpr::Float(x)) => Ok(SExpr::Float(acc $op x)),
-                    _ => Err("Type error")
-                })
-            };
+    // The procedure and arguments of `(apply procd arg ... list)`.
+    fn spread_arguments(&self, args: &[SExpr]) -> Result<(SExpr, Vec<SExpr>), &'static str> {
+        let (&procd, rest) = args.split_first().ok_or("wrong arity")?;
+        let (&last, init) = rest.split_last().ok_or("wrong arity")?;
+        if !is_list(&self.mem, last) {
+            return Err("type error: expected list");
         }
+        let mut operands = init.to_vec();
+        operands.extend(self.mem.vec_from_list(last).unwrap());
+        Ok((procd, operands))
+    }
 
-        macro_rules! afold1 {
-            ( $operands:expr, $initial:expr, $op:tt ) => {
-                arithmetic_fold!($operands.iter(), $initial, $op)
-            };
+    /// Looks up `name` in the global environment.
+    pub fn lookup(&self, name: &str) -> Result<SExpr, &'static str> {
+        let sym = self.mem.find_symbol(name).ok_or("Unbound variable")?;
+        self.eval_var(sym, self.initial_env)
+    }
+
+    // Evaluates each of `operands`, keeping the values in the roots.
+    fn eval_sequence(&mut self, operands: SExpr, env: SExpr) -> Result<Vec<SExpr>, &'static str> {
+        let vec_op = self.mem.vec_from_list(operands).or(Err("Ill formed list"))?;
+        let mut values = Vec::with_capacity(vec_op.len());
+        for e in vec_op {
+            let value = self._eval(e, env)?;
+            self.roots.push(value);
+            values.push(value);
         }
+        Ok(values)
+    }
 
-        macro_rules! afold2 {
-            ( $operands:expr, $op:tt ) => {
-                {
-                    let operands = $operands;
-                    if operands.len() >= 1 {
-                        let mut iter = operands.iter();
-                        let first = *iter.next().unwrap();
-                        arithmetic_fold!(iter, first, $op)
-                    } else {
-                        Err("Not enough operands")
-                    }
-                }
-            };
+    // Evaluates all but the last of `body`, which is returned for the caller
+    // to evaluate in tail position.
+    fn eval_body(&mut self, body: SExpr, env: SExpr) -> Result<Option<SExpr>, &'static str> {
+        let exprs = self.mem.vec_from_list(body).or(Err("Ill formed list"))?;
+        let (&last, init) = match exprs.split_last() {
+            Some(split) => split,
+            None => return Ok(None),
+        };
+        for &e in init {
+            self._eval(e, env)?;
         }
-        
-        macro_rules! comparison_fold {
-            ( $operands:expr, $op:tt) => {
-                {
-                    let operands = $operands;
-                    if operands.len() == 0 {
-                        Ok(SExpr::Bool(true))
-                    } else {
-                        let mut prev = operands[0];
-                        let mut acc = Ok(true);
-                        for &e in &operands[1..] {
-                            match acc {
-                                Ok(true) => (),
-                                Ok(false) => break,
-                                Err(_) => break,
-                            }
-                            let (accp, prevp) = match (prev, e) {
-                                (SExpr::Int(prev), SExpr::Int(x)) => (Ok(prev $op x), SExpr::Int(x)),
-                                (SExpr::Int(prev), SExpr::Float(x)) => (Ok((prev as f64) $op x), SExpr::Float(x)),
-                                (SExpr::Float(prev), SExpr::Int(x)) => (Ok(prev $op x as f64), SExpr::Int(x)),
-                                (SExpr::Float(prev), SExpr::Float(x)) => (Ok(prev $op x), SExpr::Float(x)),
-                                _ => (Err("Type error"), prev),
-                            };
-                            acc = accp;
-                            prev = prevp;
-                        }
-                        match acc {
-                            Ok(res) => Ok(SExpr::Bool(res)),
-                            Err(s) => Err(s),
-                        }
-                    }
-                }
-            };
+        Ok(Some(last))
+    }
+
+    fn number(&self, e: SExpr) -> Result<Number, &'static str> {
+        Number::from_sexpr(e, &self.mem).or(Err("Type error"))
+    }
+
+    fn arithmetic_fold<F>(&mut self, initial: Number, operands: &[SExpr], op: F) -> Result<SExpr, &'static str>
+        where F: Fn(&Number, &Number) -> Result<Number, &'static str>
+    {
+        let mut acc = initial;
+        for &e in operands {
+            acc = op(&acc, &self.number(e)?)?;
         }
+        Ok(acc.to_sexpr(&mut self.mem))
+    }
 
+    fn comparison_fold<F>(&self, operands: &[SExpr], test: F) -> Result<SExpr, &'static str>
+        where F: Fn(Ordering) -> bool
+    {
+        let numbers = operands.iter().map(|&e| self.number(e)).collect::<Result<Vec<_>, _>>()?;
+        let holds = numbers.windows(2).all(|pair| numbers::compare(&pair[0], &pair[1]).is_some_and(&test));
+        Ok(SExpr::Bool(holds))
+    }
+
+    fn eval_primitive(&mut self, procd: Primitive, operands: Vec<SExpr>) -> Result<SExpr, &'static str> {
         match procd {
-            Primitive::Add => afold1!(operands, SExpr::Int(0), +),
-            Primitive::Sub => afold2!(operands, -),
-            Primitive::Mul => afold1!(operands, SExpr::Int(1), *),
-            Primitive::Div => afold2!(operands, /),
-            Primitive::Eql => comparison_fold!(operands, ==),
-            Primitive::Lt  => comparison_fold!(operands, <),
-            Primitive::Lte => comparison_fold!(operands, <=),
-            Primitive::Gt  => comparison_fold!(operands, >),
-            Primitive::Gte => comparison_fold!(operands, >=),
+            Primitive::Add => self.arithmetic_fold(Number::Int(0), &operands, |acc, x| Ok(numbers::add(acc, x))),
+            Primitive::Sub => match *operands {
+                [] => Err("Not enough operands"),
+                [x] => self.arithmetic_fold(Number::Int(0), &[x], |acc, x| Ok(numbers::sub(acc, x))),
+                [first, ref rest @ ..] => {
+                    let first = self.number(first)?;
+                    self.arithmetic_fold(first, rest, |acc, x| Ok(numbers::sub(acc, x)))
+                }
+            }
+            Primitive::Mul => self.arithmetic_fold(Number::Int(1), &operands, |acc, x| Ok(numbers::mul(acc, x))),
+            Primitive::Div => match *operands {
+                [] => Err("Not enough operands"),
+                [x] => self.arithmetic_fold(Number::Int(1), &[x], numbers::div),
+                [first, ref rest @ ..] => {
+                    let first = self.number(first)?;
+                    self.arithmetic_fold(first, rest, numbers::div)
+                }
+            }
+            Primitive::Eql => self.comparison_fold(&operands, |o| o == Ordering::Equal),
+            Primitive::Lt  => self.comparison_fold(&operands, |o| o == Ordering::Less),
+            Primitive::Lte => self.comparison_fold(&operands, |o| o != Ordering::Greater),
+            Primitive::Gt  => self.comparison_fold(&operands, |o| o == Ordering::Greater),
+            Primitive::Gte => self.comparison_fold(&operands, |o| o != Ordering::Less),
             Primitive::Car => if operands.len() == 1 {
                 self.mem.car(operands[0]).or(Err("type error"))
             } else {
@@ -234,12 +760,14 @@ impl<'s> Interpreter<'s> {
                 Err("wrong arity")
             }
             Primitive::List => Ok(self.mem.list_from_vec(operands)),
+            // handled by `apply_step`, which calls the procedure in tail position
+            Primitive::Apply => unreachable!(),
         }
     }
 
-    fn eval_lambda(&mut self, form: SExpr<'s>, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    fn eval_lambda(&mut self, form: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
         let form_vec = self.mem.vec_from_list(form).or(Err("ill formed"))?;
-        if form_vec.len() < 1 { 
+        if form_vec.is_empty() {
             Err("ill formed")
         } else {
             let params = form_vec[0];
@@ -248,9 +776,43 @@ impl<'s> Interpreter<'s> {
         }
     }
 
-    fn make_procedure(&mut self, params: SExpr<'s>, body: SExpr<'s>, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    fn eval_delay(&mut self, form: SExpr, env: SExpr, lazy: bool, ill_formed: &'static str)
+        -> Result<SExpr, &'static str>
+    {
+        match self.mem.vec_from_list(form).or(Err(ill_formed))?[..] {
+            [expr] => Ok(self.mem.alloc(Object::Promise(Promise::Delayed { expr, env, lazy }))),
+            _ => Err(ill_formed),
+        }
+    }
+
+    // (stream-cons a b) is a stream pair whose car and cdr are both delayed
+    fn eval_stream_cons(&mut self, form: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
+        match self.mem.vec_from_list(form).or(Err("ill formed stream-cons"))?[..] {
+            [first, rest] => {
+                let first = self.mem.alloc(Object::Promise(Promise::Delayed { expr: first, env, lazy: false }));
+                let rest = self.mem.alloc(Object::Promise(Promise::Delayed { expr: rest, env, lazy: true }));
+                let pair = self.mem.cons(first, rest);
+                Ok(self.mem.alloc(Object::Promise(Promise::Forced(pair))))
+            }
+            _ => Err("ill formed stream-cons"),
+        }
+    }
+
+    // (cons-stream a b) => (cons a (delay b))
+    fn eval_cons_stream(&mut self, form: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
+        match self.mem.vec_from_list(form).or(Err("ill formed cons-stream"))?[..] {
+            [first, expr] => {
+                let first = self._eval(first, env)?;
+                let rest = self.mem.alloc(Object::Promise(Promise::Delayed { expr, env, lazy: false }));
+                Ok(self.mem.cons(first, rest))
+            }
+            _ => Err("ill formed cons-stream"),
+        }
+    }
+
+    fn make_procedure(&mut self, params: SExpr, body: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
         let param_vec = self.mem.vec_from_list(params).or(Err("err"))?;
-        if param_vec.iter().any(|e| if let SExpr::Sym(_) = e { false } else { true }) {
+        if param_vec.iter().any(|e| !matches!(e, SExpr::Sym(_))) {
             Err("ill formed")
         } else {
             let list = self.mem.list_from_vec(vec![params, body, env]);
@@ -259,12 +821,17 @@ impl<'s> Interpreter<'s> {
         }
     }
 
-    fn eval_compound(&mut self, procd: SExpr<'s>, operands: Vec<SExpr<'s>>) -> Result<SExpr<'s>, &'static str> {
+    // The body of the compound procedure `procd` and a new environment
+    // binding its parameters to `operands`.
+    fn bind_arguments(&mut self, procd: SExpr, operands: Vec<SExpr>) -> Result<(SExpr, SExpr), &'static str> {
         let vec = self.mem.vec_from_list(procd).or(Err("Ill formed procedure"))?;
         let params = vec[0];
         let body = vec[1];
         let env = vec[2];
         let param_vec = self.mem.vec_from_list(params).or(Err("something"))?;
+        if param_vec.len() != operands.len() {
+            return Err("wrong arity");
+        }
         let mut envp = Environment::new(env);
         for (param, arg) in param_vec.iter().zip(operands) {
             if let &SExpr::Sym(s) = param {
@@ -272,11 +839,10 @@ impl<'s> Interpreter<'s> {
             }
         }
         let renvp = self.mem.alloc(Object::Env(envp));
-        let res = self.eval_sequence(body, renvp)?;
-        Ok(res[res.len()-1])
+        Ok((body, renvp))
     }
 
-    fn eval_define(&mut self, form: SExpr<'s>, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    fn eval_define(&mut self, form: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
         let vec = self.mem.vec_from_list(form).or(Err("ill formed form"))?;
         if vec.len() < 2 {
             Err("ill formed define")
@@ -291,7 +857,7 @@ impl<'s> Interpreter<'s> {
                 }
                 l @ SExpr::Ref(_) => {
                     let fn_vec = self.mem.vec_from_list(l).or(Err("err"))?;
-                    if fn_vec.len() == 0 {
+                    if fn_vec.is_empty() {
                         Err("ill formed")
                     } else {
                         let ident = if let SExpr::Sym(s) = fn_vec[0] { s } else { unreachable!() };
@@ -307,7 +873,69 @@ impl<'s> Interpreter<'s> {
         }
     }
 
-    fn eval_set(&mut self, form: SExpr<'s>, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    // (define-record-type name (constructor field ...) predicate (field accessor [modifier]) ...)
+    fn eval_define_record_type(&mut self, form: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
+        const ILL_FORMED: &str = "ill formed define-record-type";
+        let vec = self.mem.vec_from_list(form).or(Err(ILL_FORMED))?;
+        let (name, constructor, predicate) = match vec[..] {
+            [SExpr::Sym(name), constructor, SExpr::Sym(predicate), ..] => (name, constructor, predicate),
+            _ => return Err(ILL_FORMED),
+        };
+        let mut fields = Vec::new();
+        let mut procedures = Vec::new();
+        for (slot, &spec) in vec[3..].iter().enumerate() {
+            match self.mem.vec_from_list(spec).or(Err(ILL_FORMED))?[..] {
+                [SExpr::Sym(field), SExpr::Sym(accessor)] => {
+                    fields.push(field);
+                    procedures.push((accessor, slot, false));
+                }
+                [SExpr::Sym(field), SExpr::Sym(accessor), SExpr::Sym(modifier)] => {
+                    fields.push(field);
+                    procedures.push((accessor, slot, false));
+                    procedures.push((modifier, slot, true));
+                }
+                _ => return Err(ILL_FORMED),
+            }
+        }
+        let constructor = match constructor {
+            SExpr::Sym(ident) => Some((ident, (0..fields.len()).collect())),
+            SExpr::Bool(false) => None,
+            list => {
+                let mut params = self.mem.vec_from_list(list).or(Err(ILL_FORMED))?.into_iter();
+                let ident = match params.next() {
+                    Some(SExpr::Sym(ident)) => ident,
+                    _ => return Err(ILL_FORMED),
+                };
+                let slots = params.map(|param| match param {
+                    SExpr::Sym(param) => fields.iter().position(|&f| f == param).ok_or("unknown field in record constructor"),
+                    _ => Err(ILL_FORMED),
+                }).collect::<Result<Vec<_>, _>>()?;
+                Some((ident, slots))
+            }
+        };
+
+        let type_name = self.mem.symbol_name(name).to_string();
+        let rtype = self.mem.alloc(Object::RecordType(RecordType { name: type_name, fields }));
+        let mut bindings = vec![(name, rtype)];
+        if let Some((ident, slots)) = constructor {
+            bindings.push((ident, self.mem.alloc(Object::RecordProcedure(RecordProcedure::Constructor(rtype, slots)))));
+        }
+        bindings.push((predicate, self.mem.alloc(Object::RecordProcedure(RecordProcedure::Predicate(rtype)))));
+        for (ident, slot, modifier) in procedures {
+            let procd = if modifier {
+                RecordProcedure::Modifier(rtype, slot)
+            } else {
+                RecordProcedure::Accessor(rtype, slot)
+            };
+            bindings.push((ident, self.mem.alloc(Object::RecordProcedure(procd))));
+        }
+        for (ident, value) in bindings {
+            self.mem.env_insert(env, ident, value).or(Err("err"))?;
+        }
+        Ok(SExpr::Sym(name))
+    }
+
+    fn eval_set(&mut self, form: SExpr, env: SExpr) -> Result<SExpr, &'static str> {
         let vec = self.mem.vec_from_list(form).or(Err("ill formed"))?;
         if vec.len() == 2 {
             if let SExpr::Sym(ident) = vec[0] {
@@ -322,21 +950,22 @@ impl<'s> Interpreter<'s> {
         }
     }
 
-    fn eval_if(&mut self, form: SExpr<'s>, env: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    // The branch to evaluate, if there is one.
+    fn eval_if(&mut self, form: SExpr, env: SExpr) -> Result<Option<SExpr>, &'static str> {
         let form_vec = self.mem.vec_from_list(form).or(Err("ill formed"))?;
-        if form_vec.len() == 3 {
+        if form_vec.len() == 2 || form_vec.len() == 3 {
             let cond = self._eval(form_vec[0], env)?;
             if cond.as_bool() {
-                self._eval(form_vec[1], env)
+                Ok(Some(form_vec[1]))
             } else {
-                self._eval(form_vec[2], env)
+                Ok(form_vec.get(2).copied())
             }
         } else {
             Err("ill formed")
         }
     }
 
-    fn eval_quote(&self, form: SExpr<'s>) -> Result<SExpr<'s>, &'static str> {
+    fn eval_quote(&self, form: SExpr) -> Result<SExpr, &'static str> {
         let form_vec = self.mem.vec_from_list(form).or(Err("ill formed"))?;
         if form_vec.len() == 1 {
             Ok(form_vec[0])
@@ -348,21 +977,30 @@ impl<'s> Interpreter<'s> {
 
 
 
+/// Evaluates `s` in a fresh interpreter, returning what it prints or the
+/// error message. Shared by the tests of the procedure libraries.
+#[cfg(test)]
+pub(crate) fn eval_to_string(s: &str) -> String {
+    let mut interpreter = Interpreter::new(2000);
+    match interpreter.eval_string(s) {
+        Ok(res) => interpreter.to_string(res),
+        Err(err) => err.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn f<'s>(x: f64) -> SExpr<'s> { SExpr::Float(x) }
-    fn i<'s>(x: i64) -> SExpr<'s> { SExpr::Int(x) }
-    fn b<'s>(x: bool) -> SExpr<'s> { SExpr::Bool(x) }
-    fn sy<'s>(x: &'s str) -> SExpr<'s> { SExpr::Sym(x) }
-    fn st<'s>(x: &'s str) -> SExpr<'s> { SExpr::Str(x) }
-    fn n<'s>() -> SExpr<'s> { SExpr::Nil }
+    fn f(x: f64) -> SExpr { SExpr::Float(x) }
+    fn i(x: i64) -> SExpr { SExpr::Int(x) }
+    fn b(x: bool) -> SExpr { SExpr::Bool(x) }
+    fn n() -> SExpr { SExpr::Nil }
 
     macro_rules! eval_ok {
         ($s:expr, $ex:expr) => {
             {
-                let mut interpreter = Interpreter::new(500);
+                let mut interpreter = Interpreter::new(600);
                 let res = interpreter.eval_string($s).expect("err");
                 assert_eq!(res, $ex);
             }
@@ -371,7 +1009,7 @@ mod tests {
     macro_rules! eval_ok_str {
         ($s:expr, $ex:expr) => {
             {
-                let mut interpreter = Interpreter::new(500);
+                let mut interpreter = Interpreter::new(600);
                 let res = interpreter.eval_string($s).expect("err");
                 assert_eq!(interpreter.mem.to_string(res), $ex);
             }
@@ -384,7 +1022,7 @@ mod tests {
         eval_ok!("2", i(2));
         eval_ok!("1.0", f(1.0));
         eval_ok!("()", n());
-        eval_ok!("\"abc\"", st("abc"));
+        eval_ok_str!("\"abc\"", "\"abc\"");
         eval_ok!("#t", b(true));
         eval_ok!("#f", b(false));
     }
@@ -394,7 +1032,7 @@ mod tests {
         eval_ok!("(+ 1 2)", i(3));
         eval_ok!("(+)", i(0));
         eval_ok!("(+ (+ 1 2) (+ 3 4))", i(10));
-        eval_ok!("(+ 1 2 3 4 5 6 7 8)", i((1..9).fold(0, |acc, x| acc+x)));
+        eval_ok!("(+ 1 2 3 4 5 6 7 8)", i((1..9).sum()));
         eval_ok!("(+ 1.0 0.5)", f(1.5));
         eval_ok!("(+ 1 0.5)", f(1.5));
         eval_ok!("(+ 1.0 2)", f(3.0));
@@ -489,6 +1127,31 @@ mod tests {
         eval_ok!("(if (= 1 2) 1 2)", i(2));
     }
 
+    #[test]
+    fn test_derived_forms() {
+        eval_ok!("(if #f 1)", SExpr::Unspecified);
+        eval_ok!("(cond ((= 1 2) 1) ((= 1 1) 2) (else 3))", i(2));
+        eval_ok!("(cond ((= 1 2) 1) (else 2 3))", i(3));
+        eval_ok!("(cond ((= 1 2) 1))", SExpr::Unspecified);
+        eval_ok!("(cond)", SExpr::Unspecified);
+        eval_ok!("(let ((a 1) (b 2)) (+ a b))", i(3));
+        eval_ok!("(let loop ((i 0) (acc 0)) (if (= i 5) acc (loop (+ i 1) (+ acc i))))", i(10));
+        eval_ok!("(define loop 3) (let loop ((i loop)) (if (= i 0) 0 (loop (- i 1))))", i(0));
+        eval_ok!("(eval (quote (let ((a 1)) (cond (#f 0) (else a)))))", i(1));
+
+        // derived forms are expanded once, not each time they are evaluated
+        let mut interpreter = Interpreter::new(1000);
+        let mut allocations = Vec::new();
+        for def in ["(define (f n) (if (= n 0) 0 (f (- n 1))))",
+                    "(define (f n) (cond ((= n 0) 0) (else (f (- n 1)))))"] {
+            interpreter.eval_string(def).unwrap();
+            let before = interpreter.memory().allocations();
+            interpreter.eval_string("(f 100)").unwrap();
+            allocations.push(interpreter.memory().allocations() - before);
+        }
+        assert_eq!(allocations[0], allocations[1]);
+    }
+
     #[test]
     fn test_recursion() {
         eval_ok!("
@@ -501,7 +1164,7 @@ mod tests {
                 )
                 (factorial 10)
             )
-        ", i((1..11).fold(1, |a,b| a*b)));
+        ", i((1..11).product()));
 
         eval_ok_str!("(begin
             (define (map fn list) 
@@ -516,10 +1179,75 @@ mod tests {
             (map (lambda (x) (* x x)) 
                       (quote (1 2 3))
                  )
-            )
         )", "(1 4 9)");
     }
 
+    #[test]
+    fn test_tail_calls() {
+        // far more iterations than the Rust stack allows for, or than fit
+        // in memory without collecting garbage
+        eval_ok_str!("(begin
+            (define (count n) (if (= n 0) (quote done) (count (- n 1))))
+            (count 20000)
+        )", "done");
+        eval_ok!("(begin
+            (define total 0)
+            (let loop ((i 0))
+                (set! total (+ total i))
+                (cond ((< i 20000) (loop (+ i 1)))
+                      (else total)))
+        )", i(200010000));
+        eval_ok!("(begin
+            (define (even n) (if (= n 0) #t (odd (- n 1))))
+            (define (odd n) (if (= n 0) #f (even (- n 1))))
+            (even 20001)
+        )", SExpr::Bool(false));
+    }
+
+    #[test]
+    fn test_garbage_collection() {
+        let mut interpreter = Interpreter::new(600);
+        interpreter.eval_string("(define kept (list 1 2 3)) (list 4 5 6)").unwrap();
+        assert!(interpreter.collect_garbage() >= 3);
+        let res = interpreter.eval_string("kept").unwrap();
+        assert_eq!(interpreter.to_string(res), "(1 2 3)");
+        assert_eq!(interpreter.eval_string("(define (f) (+ 1 (f))) (f)"), Err("Out of memory"));
+        let res = interpreter.eval_string("(define (count n) (if (= n 0) kept (count (- n 1)))) (count 5000)").unwrap();
+        assert_eq!(interpreter.to_string(res), "(1 2 3)");
+    }
+
+    #[test]
+    fn test_values_held_by_rust() {
+        let mut interpreter = Interpreter::new(600);
+        let square = interpreter.eval_string("(lambda (x) (* x x))").unwrap();
+        interpreter.eval_string("(define (count n) (if (= n 0) 0 (count (- n 1)))) (count 5000)").unwrap();
+        assert_eq!(interpreter.apply(square, &[i(3)]), Ok(i(9)));
+        assert_eq!(interpreter.to_string(square), "#<procedure>");
+
+        interpreter.define_native("after-eval", 1, |interp, args| {
+            interp.eval_string("(count 5000)")?;
+            Ok(interp.to_string(args[0]))
+        }).unwrap();
+        let res = interpreter.eval_string("(after-eval (list 1 2))").unwrap();
+        assert_eq!(interpreter.to_string(res), "\"(1 2)\"");
+
+        let handle = interpreter.protect(square);
+        interpreter.collect_garbage();
+        assert_eq!(interpreter.apply(square, &[i(4)]), Ok(i(16)));
+        interpreter.release(handle);
+        interpreter.collect_garbage();
+        assert_eq!(interpreter.to_string(square), "#<empty>");
+    }
+
+    #[test]
+    fn test_callbacks_from_natives() {
+        // every call allocates, far more in total than the heap holds
+        let mut interpreter = Interpreter::new(1000);
+        let res = interpreter.eval_string("(define n 0) \
+            (for-each (lambda (i) (set! n (+ n (length (list 1 2 3 4 5 6 7 8 9 10))))) (iota 300)) n").unwrap();
+        assert_eq!(res, i(3000));
+    }
+
     #[test]
     fn test_closures() {
         eval_ok!("(begin
@@ -563,7 +1291,7 @@ mod tests {
 
     #[test]
     fn test_quote() {
-        eval_ok!("(quote a)", sy("a"));
+        eval_ok_str!("(quote a)", "a");
         eval_ok_str!("(quote (1 2 3))", "(1 2 3)");
         eval_ok!("(car (quote (1 2 3)))", i(1));
         eval_ok!("(car (cdr (quote (1 2))))", i(2));
@@ -583,14 +1311,135 @@ mod tests {
         eval_ok_str!("(list (list 1 2 3) (list 4 5 6))", "((1 2 3) (4 5 6))");
     }
 
+    #[test]
+    fn test_eval_string() {
+        eval_ok!("(define a 2) (define b 3) (* a b)", i(6));
+        let mut interpreter = Interpreter::new(500);
+        assert_eq!(interpreter.eval_string(""), Err("Empty expression"));
+        assert_eq!(interpreter.eval_string("(+ 1"), Err("Missing closing parenthesis"));
+        assert_eq!(interpreter.eval_string("\"abc"), Err("Missing quote '\"'"));
+    }
+
+    #[test]
+    fn test_bound_names() {
+        let mut interpreter = Interpreter::new(500);
+        assert!(interpreter.bound_names().contains(&"set-car!"));
+        assert!(!interpreter.bound_names().contains(&"square"));
+        interpreter.eval_string("(define (square x) (* x x))").unwrap();
+        assert!(interpreter.bound_names().contains(&"square"));
+    }
+
+    #[test]
+    fn test_embedding() {
+        let mut interpreter = Interpreter::default();
+        interpreter.define("xs", vec![1, 2, 3]).unwrap();
+        interpreter.define("scale", 2.5).unwrap();
+        let res = interpreter.eval_string("(* scale (car (cdr xs)))").unwrap();
+        assert_eq!(interpreter.from_sexpr::<f64>(res), Ok(5.0));
+        let res = interpreter.eval_string("(cons 0 xs)").unwrap();
+        assert_eq!(interpreter.from_sexpr::<Vec<i64>>(res), Ok(vec![0, 1, 2, 3]));
+        assert_eq!(interpreter.from_sexpr::<bool>(res), Err("type error: expected boolean"));
+    }
+
+    #[test]
+    fn test_native() {
+        let mut interpreter = Interpreter::new(500);
+        interpreter.define_native("add3", 3, |interp, args| {
+            let (a, b, c): (i64, i64, i64) = interp.args(args)?;
+            Ok(a + b + c)
+        }).unwrap();
+        interpreter.define_native("sum", Arity::AtLeast(0), |interp, args| {
+            let xs: Vec<f64> = interp.args(args)?;
+            Ok(xs.iter().sum::<f64>())
+        }).unwrap();
+        interpreter.define_native("twice", 1, |interp, args| {
+            interp.eval_string("(define called #t)")?;
+            Ok(vec![args[0], args[0]])
+        }).unwrap();
+        assert_eq!(interpreter.eval_string("(add3 1 2 (add3 1 1 1))"), Ok(i(6)));
+        assert_eq!(interpreter.eval_string("(add3 1 2)"), Err("wrong arity"));
+        assert_eq!(interpreter.eval_string("(add3 1 2 #t)"), Err("type error: expected integer"));
+        assert_eq!(interpreter.eval_string("(sum 1 2.5)"), Ok(f(3.5)));
+        assert_eq!(interpreter.eval_string("(sum)"), Ok(f(0.0)));
+        assert_eq!(interpreter.eval_string("((lambda (f) (f 1 1 1)) add3)"), Ok(i(3)));
+        let res = interpreter.eval_string("(twice 5)").unwrap();
+        assert_eq!(interpreter.to_string(res), "(5 5)");
+        assert_eq!(interpreter.eval_string("called"), Ok(b(true)));
+        let res = interpreter.eval_string("add3").unwrap();
+        assert_eq!(interpreter.to_string(res), "#<native add3>");
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut interpreter = Interpreter::new(500);
+        interpreter.eval_string("(define (add a b) (+ a b)) (define (bad x) (car x))").unwrap();
+        interpreter.define_native("neg", 1, |interp, args| {
+            let x: i64 = interp.from_sexpr(args[0])?;
+            Ok(-x)
+        }).unwrap();
+        let add = interpreter.lookup("add").unwrap();
+        assert_eq!(interpreter.apply(add, &[i(1), i(2)]), Ok(i(3)));
+        assert_eq!(interpreter.apply(add, &[i(1)]), Err("wrong arity"));
+        let plus = interpreter.lookup("+").unwrap();
+        assert_eq!(interpreter.apply(plus, &[i(1), i(2), i(3)]), Ok(i(6)));
+        let neg = interpreter.lookup("neg").unwrap();
+        assert_eq!(interpreter.apply(neg, &[i(4)]), Ok(i(-4)));
+        let bad = interpreter.lookup("bad").unwrap();
+        assert_eq!(interpreter.apply(bad, &[i(4)]), Err("type error"));
+        assert_eq!(interpreter.apply(i(1), &[]), Err("Applying non procedure"));
+        assert_eq!(interpreter.lookup("missing"), Err("Unbound variable"));
+
+        let closure = interpreter.eval_string("(let ((n 10)) (lambda (x) (set! n (+ n x)) n))").unwrap();
+        assert_eq!(interpreter.apply(closure, &[i(1)]), Ok(i(11)));
+        assert_eq!(interpreter.apply(closure, &[i(1)]), Ok(i(12)));
+        assert_eq!(interpreter.eval_string("((lambda (x)) 1)"), Err("empty procedure body"));
+        assert_eq!(interpreter.eval_string("(1 2)"), Err("Applying non procedure"));
+    }
+
+    #[test]
+    fn test_host_objects() {
+        use crate::host::HostObject;
+        use std::cell::Cell;
+
+        struct Handle(Rc<Cell<bool>>);
+
+        let closed = Rc::new(Cell::new(false));
+        let mut interpreter = Interpreter::new(500);
+        let c = closed.clone();
+        interpreter.define_native("open", 0, move |_, _| {
+            Ok(HostObject::new("handle", Handle(c.clone()))
+                .with_finalizer(|h: Handle| h.0.set(true)))
+        }).unwrap();
+        interpreter.define_native("handle?", 1, |interp, args| {
+            Ok(interp.host_ref::<Handle>(args[0]).is_ok())
+        }).unwrap();
+        assert_eq!(interpreter.eval_string("(define h (open)) (handle? h)"), Ok(b(true)));
+        assert_eq!(interpreter.eval_string("(handle? 1)"), Ok(b(false)));
+        assert_eq!(interpreter.eval_string("((lambda (x) x) h)").map(|e| interpreter.to_string(e)), Ok("#<handle>".to_string()));
+        let h = interpreter.lookup("h").unwrap();
+        assert_eq!(interpreter.host_ref::<i64>(h).err(), Some("type error: wrong host object type"));
+        assert_eq!(interpreter.host_mut::<Handle>(i(1)).err(), Some("type error: expected host object"));
+        assert!(!closed.get());
+        drop(interpreter);
+        assert!(closed.get());
+    }
+
     #[test]
     #[should_panic(expected="Out of memory")]
+    fn out_of_memory_on_creation() {
+        Interpreter::new(50);
+    }
+
+    #[test]
     fn out_of_memory() {
-        let mut interpreter = Interpreter::new(50);
+        let mut interpreter = Interpreter::new(600);
         let res = interpreter.eval_string("(begin
-            (define (fn) (fn))
+            (define (fn) (+ 1 (fn)))
             (fn)
         )");
-        println!("{:?}", res)
+        assert_eq!(res, Err("Out of memory"));
+        let big = vec![0i64; 1000];
+        assert_eq!(interpreter.to_sexpr(big), Err("Out of memory"));
+        assert_eq!(interpreter.eval_string("(+ 1 2)"), Ok(SExpr::Int(3)));
     }
 }
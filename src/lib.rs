@@ -0,0 +1,60 @@
+//! An embeddable Scheme interpreter.
+//!
+//! An [`Interpreter`] owns a fixed-size [`Memory`] holding every pair,
+//! procedure and environment. Values handed back to Rust are [`SExpr`]s:
+//! immediates like integers and booleans are stored inline, everything else
+//! is an `SExpr::Ref` into that memory and is only meaningful together with
+//! the interpreter that produced it.
+//!
+//! ```
+//! use rscheme::Interpreter;
+//!
+//! let mut interpreter = Interpreter::default();
+//! interpreter.define("limit", 10).unwrap();
+//! interpreter.eval_string("(define (double x) (* 2 x))").unwrap();
+//!
+//! let res = interpreter.eval_string("(list (double limit) (double 1.5))").unwrap();
+//! assert_eq!(interpreter.to_string(res), "(20 3.0)");
+//! assert_eq!(interpreter.from_sexpr::<Vec<f64>>(res), Ok(vec![20.0, 3.0]));
+//!
+//! let err = interpreter.eval_string("(double missing)");
+//! assert_eq!(err, Err("Unbound variable"));
+//! ```
+
+mod sexpr;
+mod scanner;
+mod parser;
+mod interpreter;
+mod convert;
+mod native;
+mod host;
+mod symbol;
+mod expander;
+mod bigint;
+mod numbers;
+mod chars;
+mod vectors;
+mod bytevectors;
+mod predicates;
+mod lists;
+mod evaluation;
+mod equivalence;
+mod hashtables;
+mod records;
+mod promises;
+mod streams;
+mod gc;
+mod strings;
+
+pub use crate::scanner::{Scanner, Token, ParseError, MISSING_QUOTE};
+pub use crate::interpreter::{Interpreter, Handle, Primitive, SPECIAL_FORMS, DEFAULT_MEMORY_SIZE};
+pub use crate::sexpr::{SExpr, Object, Memory, Environment};
+pub use crate::convert::{ToSExpr, FromSExpr};
+pub use crate::native::{Native, Arity, FromArgs};
+pub use crate::host::HostObject;
+pub use crate::symbol::Symbol;
+pub use crate::bigint::BigInt;
+pub use crate::numbers::{Number, Rational};
+pub use crate::hashtables::{HashTable, Equivalence};
+pub use crate::records::{RecordType, Record, RecordProcedure};
+pub use crate::promises::Promise;
@@ -0,0 +1,286 @@
+use crate::equivalence::{eq, eqv, equal};
+use crate::interpreter::Interpreter;
+use crate::native::{Arity, position, bound};
+use crate::numbers::{self, Number};
+use crate::predicates::is_list;
+use crate::sexpr::{SExpr, Memory};
+
+// The R7RS list procedures and the common SRFI-1 ones. Procedures that walk
+// a list check it is proper first, so a cyclic list is a type error rather
+// than an endless loop. Those that call back into the interpreter root the
+// values they build up across the calls.
+
+type Same = fn(&Memory, SExpr, SExpr) -> bool;
+
+// Names of the member and association procedures and the equivalence they
+// use when no comparison procedure is given.
+const SEARCHES: &[(&str, &str, Same)] = &[
+    ("memq", "assq", |_, a, b| eq(a, b)),
+    ("memv", "assv", eqv),
+    ("member", "assoc", equal),
+];
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("length", 1, |interp, args| Ok(list(interp, args[0])?.len() as i64));
+    interpreter.define_builtin("append", Arity::AtLeast(0), |interp, args| {
+        let (&last, init) = match args.split_last() {
+            Some(split) => split,
+            None => return Ok(SExpr::Nil),
+        };
+        let mut elements = Vec::new();
+        for &l in init {
+            elements.extend(list(interp, l)?);
+        }
+        Ok(prepend(interp, elements, last))
+    });
+    interpreter.define_builtin("reverse", 1, |interp, args| {
+        let mut elements = list(interp, args[0])?;
+        elements.reverse();
+        Ok(interp.memory_mut().list_from_vec(elements))
+    });
+    interpreter.define_builtin("list-tail", 2, |interp, args| {
+        let k = bound(interp, args[1], usize::MAX)?;
+        tail(interp, args[0], k)
+    });
+    interpreter.define_builtin("list-ref", 2, |interp, args| {
+        let elements = list(interp, args[0])?;
+        Ok(elements[position(interp, args[1], elements.len())?])
+    });
+    // improper lists and other values are returned as they are
+    interpreter.define_builtin("list-copy", 1, |interp, args| {
+        if !is_list(interp.memory(), args[0]) {
+            return Ok(args[0]);
+        }
+        let elements = list(interp, args[0])?;
+        Ok(interp.memory_mut().list_from_vec(elements))
+    });
+    interpreter.define_builtin("last-pair", 1, |interp, args| {
+        let len = list(interp, args[0])?.len();
+        if len == 0 {
+            return Err("type error: expected pair");
+        }
+        tail(interp, args[0], len - 1)
+    });
+    for &(member, assoc, same) in SEARCHES {
+        interpreter.define_builtin(member, Arity::Between(2, 3), move |interp, args| {
+            let mut rest = args[1];
+            for e in list(interp, args[1])? {
+                if matches(interp, args.get(2), same, args[0], e)? {
+                    return Ok(rest);
+                }
+                rest = interp.memory().cdr(rest).unwrap();
+            }
+            Ok(SExpr::Bool(false))
+        });
+        interpreter.define_builtin(assoc, Arity::Between(2, 3), move |interp, args| {
+            for entry in list(interp, args[1])? {
+                let key = interp.memory().car(entry).or(Err("type error: expected association list"))?;
+                if matches(interp, args.get(2), same, args[0], key)? {
+                    return Ok(entry);
+                }
+            }
+            Ok(SExpr::Bool(false))
+        });
+    }
+    interpreter.define_builtin("map", Arity::AtLeast(2), |interp, args| {
+        let mut res = Vec::new();
+        for column in columns(interp, &args[1..])? {
+            let value = interp.apply(args[0], &column)?;
+            interp.root(value);
+            res.push(value);
+        }
+        Ok(interp.memory_mut().list_from_vec(res))
+    });
+    interpreter.define_builtin("for-each", Arity::AtLeast(2), |interp, args| {
+        for column in columns(interp, &args[1..])? {
+            interp.apply(args[0], &column)?;
+        }
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("filter", 2, |interp, args| {
+        let mut res = Vec::new();
+        for e in list(interp, args[1])? {
+            if interp.apply(args[0], &[e])?.as_bool() {
+                res.push(e);
+            }
+        }
+        Ok(interp.memory_mut().list_from_vec(res))
+    });
+    interpreter.define_builtin("delete", Arity::Between(2, 3), |interp, args| {
+        let mut res = Vec::new();
+        for e in list(interp, args[1])? {
+            if !matches(interp, args.get(2), equal, args[0], e)? {
+                res.push(e);
+            }
+        }
+        Ok(interp.memory_mut().list_from_vec(res))
+    });
+    // SRFI-1 argument order: `(f element accumulator)`, seeded with the
+    // first element
+    interpreter.define_builtin("reduce", 3, |interp, args| {
+        let elements = list(interp, args[2])?;
+        let (&first, rest) = match elements.split_first() {
+            Some(split) => split,
+            None => return Ok(args[1]),
+        };
+        let mut acc = first;
+        let slot = interp.root(acc);
+        for &e in rest {
+            acc = interp.apply(args[0], &[e, acc])?;
+            interp.set_root(slot, acc);
+        }
+        Ok(acc)
+    });
+    interpreter.define_builtin("fold-left", Arity::AtLeast(3), |interp, args| {
+        let mut acc = args[1];
+        let slot = interp.root(acc);
+        for column in columns(interp, &args[2..])? {
+            let mut operands = vec![acc];
+            operands.extend(column);
+            acc = interp.apply(args[0], &operands)?;
+            interp.set_root(slot, acc);
+        }
+        Ok(acc)
+    });
+    interpreter.define_builtin("fold-right", Arity::AtLeast(3), |interp, args| {
+        let mut acc = args[1];
+        let slot = interp.root(acc);
+        for mut operands in columns(interp, &args[2..])?.into_iter().rev() {
+            operands.push(acc);
+            acc = interp.apply(args[0], &operands)?;
+            interp.set_root(slot, acc);
+        }
+        Ok(acc)
+    });
+    interpreter.define_builtin("iota", Arity::Between(1, 3), |interp, args| {
+        let count = bound(interp, args[0], usize::MAX)?;
+        let start: Number = match args.get(1) {
+            Some(&e) => interp.from_sexpr(e)?,
+            None => Number::Int(0),
+        };
+        let step: Number = match args.get(2) {
+            Some(&e) => interp.from_sexpr(e)?,
+            None => Number::Int(1),
+        };
+        let mut res = Vec::new();
+        res.try_reserve_exact(count).or(Err("list too large"))?;
+        for i in 0..count {
+            let n = numbers::add(&start, &numbers::mul(&Number::Int(i as i64), &step));
+            res.push(interp.to_sexpr(n)?);
+        }
+        Ok(interp.memory_mut().list_from_vec(res))
+    });
+}
+
+fn list(interp: &Interpreter, e: SExpr) -> Result<Vec<SExpr>, &'static str> {
+    let mem = interp.memory();
+    if !is_list(mem, e) {
+        return Err("type error: expected list");
+    }
+    Ok(mem.vec_from_list(e).unwrap())
+}
+
+// The list of `elements` followed by `tail`.
+fn prepend(interp: &mut Interpreter, elements: Vec<SExpr>, tail: SExpr) -> SExpr {
+    elements.into_iter().rev().fold(tail, |acc, e| interp.memory_mut().cons(e, acc))
+}
+
+// What is left after taking `k` cdrs.
+fn tail(interp: &Interpreter, mut l: SExpr, k: usize) -> Result<SExpr, &'static str> {
+    for _ in 0..k {
+        l = interp.memory().cdr(l).or(Err("index out of range"))?;
+    }
+    Ok(l)
+}
+
+// The i-th elements of every list, up to the length of the shortest one.
+fn columns(interp: &Interpreter, lists: &[SExpr]) -> Result<Vec<Vec<SExpr>>, &'static str> {
+    let lists = lists.iter().map(|&l| list(interp, l)).collect::<Result<Vec<_>, _>>()?;
+    let len = lists.iter().map(|l| l.len()).min().unwrap_or(0);
+    Ok((0..len).map(|i| lists.iter().map(|l| l[i]).collect()).collect())
+}
+
+// Compares with the procedure `compare` if one was given, or `same`.
+fn matches(interp: &mut Interpreter, compare: Option<&SExpr>, same: Same, a: SExpr, b: SExpr)
+    -> Result<bool, &'static str>
+{
+    match compare {
+        Some(&procd) => Ok(interp.apply(procd, &[a, b])?.as_bool()),
+        None => Ok(same(interp.memory(), a, b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::{Interpreter, eval_to_string as eval};
+
+    #[test]
+    fn test_basics() {
+        assert_eq!(eval("(length (list 1 2 3))"), "3");
+        assert_eq!(eval("(length (cons 1 2))"), "type error: expected list");
+        assert_eq!(eval("(define x (list 1)) (set-cdr! x x) (length x)"), "type error: expected list");
+        assert_eq!(eval("(append (list 1 2) (list) (list 3) 4)"), "(1 2 3 . 4)");
+        assert_eq!(eval("(list (append) (append 1))"), "(() 1)");
+        assert_eq!(eval("(reverse (list 1 2 3))"), "(3 2 1)");
+        assert_eq!(eval("(list-tail (list 1 2 3) 2)"), "(3)");
+        assert_eq!(eval("(list-tail (list 1 2 3) 4)"), "index out of range");
+        assert_eq!(eval("(list-ref (list 1 2 3) 1)"), "2");
+        assert_eq!(eval("(list-ref (list 1 2 3) 3)"), "index out of range");
+        assert_eq!(eval("(define a (list 1 2)) (define b (list-copy a)) (set-car! b 9) (list a b)"), "((1 2) (9 2))");
+        assert_eq!(eval("(last-pair (list 1 2 3))"), "(3)");
+        assert_eq!(eval("(iota 5)"), "(0 1 2 3 4)");
+        assert_eq!(eval("(iota 3 1 1/2)"), "(1 3/2 2)");
+    }
+
+    #[test]
+    fn test_searching() {
+        assert_eq!(eval("(memq (quote c) (quote (a b c d)))"), "(c d)");
+        assert_eq!(eval("(memq (quote e) (quote (a b c d)))"), "false");
+        assert_eq!(eval("(member (list 1) (list 0 (list 1) 2))"), "((1) 2)");
+        assert_eq!(eval("(memv 100000000000000000000 (list 1 100000000000000000000))"), "(100000000000000000000)");
+        assert_eq!(eval("(member 2.0 (list 1 2 3) =)"), "(2 3)");
+        assert_eq!(eval("(assq (quote b) (quote ((a 1) (b 2))))"), "(b 2)");
+        assert_eq!(eval("(assoc (list 1) (list (cons (list 1) 2)))"), "((1) . 2)");
+        assert_eq!(eval("(assv 5 (list (cons 1 2)))"), "false");
+        assert_eq!(eval("(assq 1 (list 1))"), "type error: expected association list");
+    }
+
+    #[test]
+    fn test_higher_order() {
+        assert_eq!(eval("(map (lambda (x) (* x x)) (list 1 2 3))"), "(1 4 9)");
+        assert_eq!(eval("(map + (list 1 2 3) (list 10 20))"), "(11 22)");
+        assert_eq!(eval("(define sum 0) (for-each (lambda (x y) (set! sum (+ sum (* x y)))) (list 1 2) (list 3 4)) sum"), "11");
+        assert_eq!(eval("(filter (lambda (x) (< x 3)) (list 1 5 2 4))"), "(1 2)");
+        assert_eq!(eval("(delete 2 (list 1 2 3 2))"), "(1 3)");
+        assert_eq!(eval("(delete 2 (list 1 2 3) <)"), "(1 2)");
+        assert_eq!(eval("(reduce + 0 (list 1 2 3))"), "6");
+        assert_eq!(eval("(reduce + 0 (list))"), "0");
+        assert_eq!(eval("(reduce list 0 (list 1 2 3))"), "(3 (2 1))");
+        assert_eq!(eval("(fold-left cons (list) (list 1 2 3))"), "(((() . 1) . 2) . 3)");
+        assert_eq!(eval("(fold-right cons (list) (list 1 2 3))"), "(1 2 3)");
+        assert_eq!(eval("(fold-left (lambda (acc x y) (+ acc (* x y))) 0 (list 1 2) (list 3 4))"), "11");
+        assert_eq!(eval("(fold-right list 0 (list 1 2) (list 3 4))"), "(1 3 (2 4 0))");
+    }
+
+    #[test]
+    fn test_long_lists() {
+        // each call allocates, so the heap fills up many times over while
+        // the results built so far must survive
+        let mut interpreter = Interpreter::new(2500);
+        interpreter.eval_string("(define xs (iota 300)) (define (waste x) (length (list 1 2 3 4 5 6 7 8 9 10)) x)").unwrap();
+        let tests = [
+            ("(equal? (map (lambda (x) (list (waste x))) xs) (map list xs))", "true"),
+            ("(define n 0) (for-each (lambda (x) (set! n (+ n (waste 1)))) xs) n", "300"),
+            ("(length (filter (lambda (x) (even? (waste x))) xs))", "150"),
+            ("(length (delete 0 xs (lambda (a b) (= a (waste b)))))", "299"),
+            ("(equal? (reduce (lambda (x acc) (append acc (waste x))) 0 (map list xs)) xs)", "true"),
+            ("(equal? (fold-left (lambda (acc x) (cons (waste x) acc)) (list) xs) (reverse xs))", "true"),
+            ("(equal? (fold-right (lambda (x acc) (cons (waste x) acc)) (list) xs) xs)", "true"),
+        ];
+        for (expr, expected) in tests {
+            interpreter.collect_garbage();
+            let res = interpreter.eval_string(expr).unwrap();
+            assert_eq!(interpreter.to_string(res), expected);
+        }
+    }
+}
@@ -1,14 +1,7 @@
-#![allow(dead_code)]
-
-mod sexpr;
-mod scanner;
-mod parser;
-mod interpreter;
+mod editor;
+mod repl;
 
 
 fn main() {
-    // let vec = vec!["abc".to_string(), "def".to_string()];
-    // let r = & A { vec };
-    // r.vec[0];
-    
-}
\ No newline at end of file
+    repl::run();
+}
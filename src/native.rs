@@ -0,0 +1,154 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::sexpr::{SExpr, Memory};
+use crate::interpreter::Interpreter;
+use crate::convert::FromSExpr;
+
+pub type NativeFn = dyn Fn(&mut Interpreter, &[SExpr]) -> Result<SExpr, &'static str>;
+
+/// Number of arguments a native procedure accepts.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn check(self, n: usize) -> Result<(), &'static str> {
+        match self {
+            Arity::Exactly(k) if n == k => Ok(()),
+            Arity::AtLeast(k) if n >= k => Ok(()),
+            Arity::Between(min, max) if min <= n && n <= max => Ok(()),
+            _ => Err("wrong arity"),
+        }
+    }
+}
+
+impl From<usize> for Arity {
+    fn from(n: usize) -> Self { Arity::Exactly(n) }
+}
+
+/// A procedure implemented by a Rust closure registered with
+/// `Interpreter::define_native`.
+#[derive(Clone)]
+pub struct Native {
+    pub name: String,
+    pub arity: Arity,
+    func: Rc<NativeFn>,
+}
+
+impl Native {
+    pub fn new(name: &str, arity: Arity, func: Rc<NativeFn>) -> Self {
+        Native { name: name.to_string(), arity, func }
+    }
+
+    pub fn call(&self, interpreter: &mut Interpreter, args: &[SExpr]) -> Result<SExpr, &'static str> {
+        self.arity.check(args.len())?;
+        (self.func)(interpreter, args)
+    }
+}
+
+impl PartialEq for Native {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.func, &other.func)
+    }
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Native({}, {:?})", self.name, self.arity)
+    }
+}
+
+/// Argument lists that can be converted from a slice of Scheme values,
+/// e.g. `(i64, f64)` or `Vec<i64>` for any number of integers.
+pub trait FromArgs: Sized {
+    fn from_args(args: &[SExpr], mem: &Memory) -> Result<Self, &'static str>;
+}
+
+impl<T: FromSExpr> FromArgs for Vec<T> {
+    fn from_args(args: &[SExpr], mem: &Memory) -> Result<Self, &'static str> {
+        args.iter().map(|&e| T::from_sexpr(e, mem)).collect()
+    }
+}
+
+macro_rules! tuple_from_args {
+    ( $n:expr; $( $t:ident $i:tt ),* ) => {
+        impl<$( $t: FromSExpr ),*> FromArgs for ( $( $t, )* ) {
+            #[allow(unused_variables)]
+            fn from_args(args: &[SExpr], mem: &Memory) -> Result<Self, &'static str> {
+                if args.len() != $n {
+                    return Err("wrong arity");
+                }
+                Ok(( $( $t::from_sexpr(args[$i], mem)?, )* ))
+            }
+        }
+    };
+}
+
+tuple_from_args!(0;);
+tuple_from_args!(1; A 0);
+tuple_from_args!(2; A 0, B 1);
+tuple_from_args!(3; A 0, B 1, C 2);
+tuple_from_args!(4; A 0, B 1, C 2, D 3);
+tuple_from_args!(5; A 0, B 1, C 2, D 3, E 4);
+tuple_from_args!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+
+// An index that must be below `len`.
+pub(crate) fn position(interp: &Interpreter, e: SExpr, len: usize) -> Result<usize, &'static str> {
+    let k: i64 = interp.from_sexpr(e)?;
+    if k < 0 || k as usize >= len {
+        return Err("index out of range");
+    }
+    Ok(k as usize)
+}
+
+// An index that may be one past the end, as in `(substring s 0 len)`.
+pub(crate) fn bound(interp: &Interpreter, e: SExpr, len: usize) -> Result<usize, &'static str> {
+    position(interp, e, len.saturating_add(1))
+}
+
+// The optional `start` and `end` arguments found at `args[from..]`.
+pub(crate) fn range(interp: &Interpreter, args: &[SExpr], from: usize, len: usize) -> Result<(usize, usize), &'static str> {
+    let start = match args.get(from) {
+        Some(&e) => bound(interp, e, len)?,
+        None => 0,
+    };
+    let end = match args.get(from + 1) {
+        Some(&e) => bound(interp, e, len)?,
+        None => len,
+    };
+    if start > end {
+        return Err("index out of range");
+    }
+    Ok((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arity() {
+        assert_eq!(Arity::from(2).check(2), Ok(()));
+        assert_eq!(Arity::from(2).check(1), Err("wrong arity"));
+        assert_eq!(Arity::AtLeast(1).check(3), Ok(()));
+        assert_eq!(Arity::AtLeast(1).check(0), Err("wrong arity"));
+        assert_eq!(Arity::Between(1, 3).check(3), Ok(()));
+        assert_eq!(Arity::Between(1, 3).check(4), Err("wrong arity"));
+    }
+
+    #[test]
+    fn test_from_args() {
+        let mut mem = Memory::new(10);
+        let x = mem.alloc(crate::sexpr::Object::Str(vec!['x']));
+        let args = [SExpr::Int(1), SExpr::Float(2.5), x];
+        assert_eq!(<(i64, f64, String)>::from_args(&args, &mem), Ok((1, 2.5, "x".to_string())));
+        assert_eq!(<(i64, f64)>::from_args(&args, &mem), Err("wrong arity"));
+        assert_eq!(<(f64, f64, f64)>::from_args(&args, &mem), Err("type error: expected number"));
+        assert_eq!(Vec::<f64>::from_args(&args[..2], &mem), Ok(vec![1.0, 2.5]));
+        assert_eq!(<()>::from_args(&[], &mem), Ok(()));
+    }
+}
@@ -0,0 +1,806 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::bigint::BigInt;
+use crate::interpreter::Interpreter;
+use crate::native::Arity;
+use crate::sexpr::SExpr;
+
+// Exact integers are `SExpr::Int` while they fit in an i64 and
+// `Object::BigInt` cells beyond that; other exact numbers are
+// `Object::Rational` cells. Arithmetic works on `Number`s taken out with
+// `FromSExpr`; storing one back with `ToSExpr` keeps the smallest
+// representation.
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Number {
+    Int(i64),
+    Big(BigInt),
+    Ratio(Rational),
+    Float(f64),
+}
+
+/// An exact ratio in lowest terms whose denominator is greater than one.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Rational {
+    num: BigInt,
+    den: BigInt,
+}
+
+impl Rational {
+    pub fn numerator(&self) -> &BigInt {
+        &self.num
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.den
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        if self.num.bits() <= 53 && self.den.bits() <= 53 {
+            return self.num.to_f64() / self.den.to_f64();
+        }
+        // divide with 64 significant bits so huge parts do not overflow
+        let shift = 64 + self.den.bits() as i64 - self.num.bits() as i64;
+        let scale = BigInt::from(2).pow(shift.unsigned_abs() as u32);
+        let q = if shift >= 0 {
+            self.num.mul(&scale).div_rem(&self.den)
+        } else {
+            self.num.div_rem(&self.den.mul(&scale))
+        };
+        q.unwrap().0.to_f64() * 2f64.powf(-shift as f64)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.num, self.den)
+    }
+}
+
+// An exact number as a numerator and a positive denominator.
+type Parts = (BigInt, BigInt);
+
+impl Number {
+    /// `Int` if `b` fits in one, so equal values have one representation.
+    pub fn from_big(b: BigInt) -> Number {
+        match b.to_i64() {
+            Some(i) => Number::Int(i),
+            None => Number::Big(b),
+        }
+    }
+
+    /// The exact number `num/den` in lowest terms, or `None` if `den` is zero.
+    pub fn ratio(num: BigInt, den: BigInt) -> Option<Number> {
+        if den.is_zero() {
+            return None;
+        }
+        let (num, den) = if den.is_negative() { (num.neg(), den.neg()) } else { (num, den) };
+        let g = num.gcd(&den);
+        let (num, den) = (num.div_rem(&g).unwrap().0, den.div_rem(&g).unwrap().0);
+        if den == BigInt::from(1) {
+            Some(Number::from_big(num))
+        } else {
+            Some(Number::Ratio(Rational { num, den }))
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Int(i) => i as f64,
+            Number::Big(ref b) => b.to_f64(),
+            Number::Ratio(ref r) => r.to_f64(),
+            Number::Float(f) => f,
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match *self {
+            Number::Ratio(_) => false,
+            Number::Float(f) => f.is_finite() && f.fract() == 0.0,
+            _ => true,
+        }
+    }
+
+    fn parts(&self) -> Parts {
+        match *self {
+            Number::Int(i) => (BigInt::from(i), BigInt::from(1)),
+            Number::Big(ref b) => (b.clone(), BigInt::from(1)),
+            Number::Ratio(ref r) => (r.num.clone(), r.den.clone()),
+            Number::Float(_) => unreachable!("inexact number"),
+        }
+    }
+
+    /// The exact number equal to `self`; every finite float has one.
+    pub fn to_exact(&self) -> Result<Number, &'static str> {
+        let f = match *self {
+            Number::Float(f) => f,
+            _ => return Ok(self.clone()),
+        };
+        if !f.is_finite() {
+            return Err("no exact representation");
+        }
+        // f = mantissa * 2^exp
+        let bits = f.to_bits();
+        let biased = ((bits >> 52) & 0x7ff) as i32;
+        let mut mantissa = bits & ((1 << 52) - 1);
+        if biased != 0 {
+            mantissa |= 1 << 52;
+        }
+        let exp = biased.max(1) - 1075;
+        let mut num = BigInt::from(mantissa as i64);
+        if f < 0.0 {
+            num = num.neg();
+        }
+        let scale = BigInt::from(2).pow(exp.unsigned_abs());
+        Ok(if exp >= 0 {
+            Number::from_big(num.mul(&scale))
+        } else {
+            Number::ratio(num, scale).unwrap()
+        })
+    }
+
+    pub fn to_inexact(&self) -> Number {
+        Number::Float(self.to_f64())
+    }
+}
+
+// Applies `small` to two i64s, falling back to `exact` on overflow or when
+// either side is not an i64, and to `float` when either side is inexact.
+fn combine(a: &Number, b: &Number,
+           small: fn(i64, i64) -> Option<i64>,
+           exact: fn(Parts, Parts) -> Parts,
+           float: fn(f64, f64) -> f64) -> Number {
+    if let (&Number::Int(x), &Number::Int(y)) = (a, b) {
+        if let Some(z) = small(x, y) {
+            return Number::Int(z);
+        }
+    }
+    if a.is_exact() && b.is_exact() {
+        let (num, den) = exact(a.parts(), b.parts());
+        Number::ratio(num, den).unwrap()
+    } else {
+        Number::Float(float(a.to_f64(), b.to_f64()))
+    }
+}
+
+pub fn add(a: &Number, b: &Number) -> Number {
+    combine(a, b, i64::checked_add,
+        |(n1, d1), (n2, d2)| (n1.mul(&d2).add(&n2.mul(&d1)), d1.mul(&d2)),
+        |x, y| x + y)
+}
+
+pub fn sub(a: &Number, b: &Number) -> Number {
+    combine(a, b, i64::checked_sub,
+        |(n1, d1), (n2, d2)| (n1.mul(&d2).sub(&n2.mul(&d1)), d1.mul(&d2)),
+        |x, y| x - y)
+}
+
+pub fn mul(a: &Number, b: &Number) -> Number {
+    combine(a, b, i64::checked_mul,
+        |(n1, d1), (n2, d2)| (n1.mul(&n2), d1.mul(&d2)),
+        |x, y| x * y)
+}
+
+/// Division; exact operands give an exact quotient.
+pub fn div(a: &Number, b: &Number) -> Result<Number, &'static str> {
+    if a.is_exact() && *b == Number::Int(0) {
+        return Err("division by zero");
+    }
+    Ok(combine(a, b,
+        |x, y| match x.checked_rem(y) {
+            Some(0) => x.checked_div(y),
+            _ => None,
+        },
+        |(n1, d1), (n2, d2)| (n1.mul(&d2), d1.mul(&n2)),
+        |x, y| x / y))
+}
+
+pub fn neg(a: &Number) -> Number {
+    sub(&Number::Int(0), a)
+}
+
+/// `None` when either side is NaN.
+pub fn compare(a: &Number, b: &Number) -> Option<Ordering> {
+    match (a, b) {
+        (Number::Int(x), Number::Int(y)) => Some(x.cmp(y)),
+        _ if a.is_exact() && b.is_exact() => {
+            let ((n1, d1), (n2, d2)) = (a.parts(), b.parts());
+            Some(n1.mul(&d2).cmp(&n2.mul(&d1)))
+        }
+        _ => a.to_f64().partial_cmp(&b.to_f64()),
+    }
+}
+
+/// Integer division rounding the quotient toward zero, or toward negative
+/// infinity when `floor`; gives the quotient and the remainder.
+pub fn div_integer(a: &Number, b: &Number, floor: bool) -> Result<(Number, Number), &'static str> {
+    if !a.is_integer() || !b.is_integer() {
+        return Err("type error: expected integer");
+    }
+    if sign(b) == Ordering::Equal {
+        return Err("division by zero");
+    }
+    if let (&Number::Int(x), &Number::Int(y)) = (a, b) {
+        if let (Some(q), Some(r)) = (x.checked_div(y), x.checked_rem(y)) {
+            return Ok(if floor && r != 0 && (r < 0) != (y < 0) {
+                (Number::Int(q - 1), Number::Int(r + y))
+            } else {
+                (Number::Int(q), Number::Int(r))
+            });
+        }
+    }
+    if !a.is_exact() || !b.is_exact() {
+        let (x, y) = (a.to_f64(), b.to_f64());
+        let mut r = x % y;
+        if floor && r != 0.0 && (r < 0.0) != (y < 0.0) {
+            r += y;
+        }
+        return Ok((Number::Float(((x - r) / y).round()), Number::Float(r)));
+    }
+    let (x, y) = (a.parts().0, b.parts().0);
+    let (mut q, mut r) = x.div_rem(&y).unwrap();
+    if floor && !r.is_zero() && r.is_negative() != y.is_negative() {
+        q = q.sub(&BigInt::from(1));
+        r = r.add(&y);
+    }
+    Ok((Number::from_big(q), Number::from_big(r)))
+}
+
+#[derive(Clone, Copy)]
+pub enum Rounding {
+    Floor,
+    Ceiling,
+    Round,
+    Truncate,
+}
+
+/// Rounds to an integer; `Round` breaks ties toward even.
+pub fn round(n: &Number, mode: Rounding) -> Number {
+    match *n {
+        Number::Float(f) => Number::Float(match mode {
+            Rounding::Floor => f.floor(),
+            Rounding::Ceiling => f.ceil(),
+            Rounding::Round => f.round_ties_even(),
+            Rounding::Truncate => f.trunc(),
+        }),
+        Number::Ratio(ref r) => {
+            let one = BigInt::from(1);
+            let q = r.num.div_rem(&r.den).unwrap().0;
+            // a ratio is never an integer, so it lies strictly between these
+            let floor = if r.num.is_negative() { q.sub(&one) } else { q.clone() };
+            let res = match mode {
+                Rounding::Floor => floor,
+                Rounding::Ceiling => floor.add(&one),
+                Rounding::Truncate => q,
+                Rounding::Round => {
+                    let twice_frac = r.num.sub(&floor.mul(&r.den)).mul(&BigInt::from(2));
+                    match twice_frac.cmp(&r.den) {
+                        Ordering::Less => floor,
+                        Ordering::Greater => floor.add(&one),
+                        Ordering::Equal if floor.is_even() => floor,
+                        Ordering::Equal => floor.add(&one),
+                    }
+                }
+            };
+            Number::from_big(res)
+        }
+        _ => n.clone(),
+    }
+}
+
+// Results of `expt` beyond this many bits are refused instead of computed.
+const MAX_EXPT_BITS: u64 = 1 << 24;
+
+pub fn expt(base: &Number, exp: &Number) -> Result<Number, &'static str> {
+    if !base.is_exact() || !exp.is_exact() || !exp.is_integer() {
+        return Ok(Number::Float(base.to_f64().powf(exp.to_f64())));
+    }
+    let (num, den) = base.parts();
+    let k = exp.parts().0;
+    if num.abs() == den {
+        // a base of 1 or -1 allows any exponent
+        return Ok(if num.is_negative() && !k.is_even() { Number::Int(-1) } else { Number::Int(1) });
+    }
+    let k_abs = k.abs().to_i64().and_then(|k| u32::try_from(k).ok()).ok_or("exponent too large")?;
+    if num.bits().max(den.bits()).saturating_mul(u64::from(k_abs)) > MAX_EXPT_BITS {
+        return Err("exponent too large");
+    }
+    let (num, den) = (num.pow(k_abs), den.pow(k_abs));
+    if k.is_negative() {
+        Number::ratio(den, num).ok_or("division by zero")
+    } else {
+        Ok(Number::ratio(num, den).unwrap())
+    }
+}
+
+/// An exact square root when `n` is an exact square, otherwise inexact.
+pub fn sqrt(n: &Number) -> Number {
+    if n.is_exact() && sign(n) != Ordering::Less {
+        let (num, den) = n.parts();
+        let (s, t) = (num.sqrt(), den.sqrt());
+        if s.mul(&s) == num && t.mul(&t) == den {
+            return Number::ratio(s, t).unwrap();
+        }
+    }
+    Number::Float(n.to_f64().sqrt())
+}
+
+fn sign(n: &Number) -> Ordering {
+    compare(n, &Number::Int(0)).unwrap_or(Ordering::Equal)
+}
+
+/// Parses R7RS number syntax: optional `#x`, `#b`, `#o`, `#d`, `#e` and
+/// `#i` prefixes, then an integer, a ratio like `1/3`, a decimal (in radix
+/// 10 only) or one of `+inf.0`, `-inf.0` and `+nan.0`.
+pub fn parse(s: &str, radix: u32) -> Option<Number> {
+    let mut radix = radix;
+    let mut radix_prefix = false;
+    let mut exact = None;
+    let mut rest = s;
+    while let Some(prefixed) = rest.strip_prefix('#') {
+        match prefixed.chars().next()?.to_ascii_lowercase() {
+            ch @ ('x' | 'b' | 'o' | 'd') if !radix_prefix => {
+                radix = match ch { 'x' => 16, 'b' => 2, 'o' => 8, _ => 10 };
+                radix_prefix = true;
+            }
+            ch @ ('e' | 'i') if exact.is_none() => exact = Some(ch == 'e'),
+            _ => return None,
+        }
+        rest = &prefixed[1..];
+    }
+    let n = parse_real(rest, radix, exact == Some(true))?;
+    match exact {
+        Some(true) => n.to_exact().ok(),
+        Some(false) => Some(n.to_inexact()),
+        None => Some(n),
+    }
+}
+
+fn parse_real(s: &str, radix: u32, exact: bool) -> Option<Number> {
+    match s {
+        "+inf.0" => return Some(Number::Float(f64::INFINITY)),
+        "-inf.0" => return Some(Number::Float(f64::NEG_INFINITY)),
+        "+nan.0" | "-nan.0" => return Some(Number::Float(f64::NAN)),
+        _ => (),
+    }
+    if let Some((num, den)) = s.split_once('/') {
+        if den.starts_with(['+', '-']) {
+            return None;
+        }
+        return Number::ratio(BigInt::parse(num, radix)?, BigInt::parse(den, radix)?);
+    }
+    if let Some(b) = BigInt::parse(s, radix) {
+        return Some(Number::from_big(b));
+    }
+    // `f64::from_str` also takes words like `inf`, which are no numbers here
+    let decimal = s.chars().all(|ch| ch.is_ascii_digit() || "+-.eE".contains(ch));
+    if radix != 10 || !decimal || !s.contains(|ch: char| ch.is_ascii_digit()) {
+        return None;
+    }
+    let f: f64 = s.parse().ok()?;
+    if exact { parse_exact_decimal(s) } else { Some(Number::Float(f)) }
+}
+
+// The exact value of a valid decimal like `-1.25e3`, without rounding it
+// to a float first.
+fn parse_exact_decimal(s: &str) -> Option<Number> {
+    let (mantissa, exp) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+        None => (s, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = BigInt::parse(&format!("{}{}", int, frac), 10)?;
+    let scale = expt(&Number::Int(10), &Number::Int(exp.checked_sub(frac.len() as i64)?)).ok()?;
+    Some(mul(&Number::from_big(digits), &scale))
+}
+
+/// Writes a float so that `parse` reads it back as the same float.
+pub fn float_to_string(f: f64) -> String {
+    if f.is_nan() {
+        "+nan.0".to_string()
+    } else if f.is_infinite() {
+        (if f > 0.0 { "+inf.0" } else { "-inf.0" }).to_string()
+    } else {
+        // `Debug` keeps a fractional part or an exponent, so the result
+        // never reads back as an exact integer
+        format!("{:?}", f)
+    }
+}
+
+fn to_string_radix(n: &Number, radix: u32) -> String {
+    match *n {
+        Number::Ratio(ref r) => format!("{}/{}", r.num.to_string_radix(radix), r.den.to_string_radix(radix)),
+        _ => n.parts().0.to_string_radix(radix),
+    }
+}
+
+type Predicate = fn(&Number) -> bool;
+
+// Predicates that are false for anything that is not a number.
+const TYPE_PREDICATES: &[(&str, Predicate)] = &[
+    ("number?", |_| true),
+    ("complex?", |_| true),
+    ("real?", |_| true),
+    ("rational?", |n| n.to_f64().is_finite() || n.is_exact()),
+    ("integer?", Number::is_integer),
+    ("exact-integer?", |n| n.is_exact() && n.is_integer()),
+    ("exact-rational?", Number::is_exact),
+];
+
+const PREDICATES: &[(&str, Predicate)] = &[
+    ("exact?", Number::is_exact),
+    ("inexact?", |n| !n.is_exact()),
+    ("zero?", |n| compare(n, &Number::Int(0)) == Some(Ordering::Equal)),
+    ("positive?", |n| compare(n, &Number::Int(0)) == Some(Ordering::Greater)),
+    ("negative?", |n| compare(n, &Number::Int(0)) == Some(Ordering::Less)),
+    ("nan?", |n| matches!(n, Number::Float(f) if f.is_nan())),
+    ("infinite?", |n| matches!(n, Number::Float(f) if f.is_infinite())),
+    ("finite?", |n| !matches!(n, Number::Float(f) if !f.is_finite())),
+];
+
+const ROUNDING: &[(&str, Rounding)] = &[
+    ("floor", Rounding::Floor),
+    ("ceiling", Rounding::Ceiling),
+    ("round", Rounding::Round),
+    ("truncate", Rounding::Truncate),
+];
+
+type Function = fn(f64) -> f64;
+
+const TRANSCENDENTAL: &[(&str, Function)] = &[
+    ("exp", f64::exp),
+    ("sin", f64::sin),
+    ("cos", f64::cos),
+    ("tan", f64::tan),
+    ("asin", f64::asin),
+    ("acos", f64::acos),
+];
+
+// The names of the division procedures rounding toward negative infinity
+// and toward zero. Without multiple values, `floor/` and `truncate/` return
+// a list of the quotient and the remainder.
+const DIVISIONS: &[(bool, &str, &str, &str)] = &[
+    (true, "floor/", "floor-quotient", "floor-remainder"),
+    (false, "truncate/", "truncate-quotient", "truncate-remainder"),
+];
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    for &(name, test) in TYPE_PREDICATES {
+        interpreter.define_builtin(name, 1, move |interp, args| {
+            Ok(interp.from_sexpr::<Number>(args[0]).is_ok_and(|n| test(&n)))
+        });
+    }
+    for &(name, test) in PREDICATES {
+        interpreter.define_builtin(name, 1, move |interp, args| Ok(test(&interp.from_sexpr(args[0])?)));
+    }
+    for &(name, even) in &[("odd?", false), ("even?", true)] {
+        interpreter.define_builtin(name, 1, move |interp, args| {
+            let n: Number = interp.from_sexpr(args[0])?;
+            let (_, r) = div_integer(&n, &Number::Int(2), true)?;
+            Ok((sign(&r) == Ordering::Equal) == even)
+        });
+    }
+    for &(floor, both, quotient, remainder) in DIVISIONS {
+        interpreter.define_builtin(both, 2, move |interp, args| {
+            let (a, b) = interp.args(args)?;
+            let (q, r) = div_integer(&a, &b, floor)?;
+            Ok(vec![q, r])
+        });
+        interpreter.define_builtin(quotient, 2, move |interp, args| {
+            let (a, b) = interp.args(args)?;
+            Ok(div_integer(&a, &b, floor)?.0)
+        });
+        interpreter.define_builtin(remainder, 2, move |interp, args| {
+            let (a, b) = interp.args(args)?;
+            Ok(div_integer(&a, &b, floor)?.1)
+        });
+    }
+    interpreter.define_builtin("quotient", 2, |interp, args| {
+        let (a, b) = interp.args(args)?;
+        Ok(div_integer(&a, &b, false)?.0)
+    });
+    interpreter.define_builtin("remainder", 2, |interp, args| {
+        let (a, b) = interp.args(args)?;
+        Ok(div_integer(&a, &b, false)?.1)
+    });
+    interpreter.define_builtin("modulo", 2, |interp, args| {
+        let (a, b) = interp.args(args)?;
+        Ok(div_integer(&a, &b, true)?.1)
+    });
+    interpreter.define_builtin("abs", 1, |interp, args| {
+        let n: Number = interp.from_sexpr(args[0])?;
+        Ok(match n {
+            Number::Float(f) => Number::Float(f.abs()),
+            _ if sign(&n) == Ordering::Less => neg(&n),
+            _ => n,
+        })
+    });
+    for &(name, keep) in &[("min", Ordering::Less), ("max", Ordering::Greater)] {
+        interpreter.define_builtin(name, Arity::AtLeast(1), move |interp, args| {
+            let numbers: Vec<Number> = interp.args(args)?;
+            let exact = numbers.iter().all(Number::is_exact);
+            let mut best = numbers[0].clone();
+            for n in &numbers[1..] {
+                if compare(n, &best) == Some(keep) || n.to_f64().is_nan() {
+                    best = n.clone();
+                }
+            }
+            Ok(if exact { best } else { best.to_inexact() })
+        });
+    }
+    interpreter.define_builtin("gcd", Arity::AtLeast(0), |interp, args| {
+        let numbers: Vec<Number> = interp.args(args)?;
+        fold_integers(&numbers, BigInt::from(0), |a, b| a.gcd(b))
+    });
+    interpreter.define_builtin("lcm", Arity::AtLeast(0), |interp, args| {
+        let numbers: Vec<Number> = interp.args(args)?;
+        fold_integers(&numbers, BigInt::from(1), |a, b| {
+            if a.is_zero() || b.is_zero() {
+                return BigInt::from(0);
+            }
+            a.mul(b).abs().div_rem(&a.gcd(b)).unwrap().0
+        })
+    });
+    interpreter.define_builtin("expt", 2, |interp, args| {
+        let (base, exp) = interp.args(args)?;
+        expt(&base, &exp)
+    });
+    interpreter.define_builtin("exact-integer-sqrt", 1, |interp, args| {
+        let n: Number = interp.from_sexpr(args[0])?;
+        if !n.is_exact() || !n.is_integer() || sign(&n) == Ordering::Less {
+            return Err("type error: expected exact non-negative integer");
+        }
+        let n = n.parts().0;
+        let s = n.sqrt();
+        let r = n.sub(&s.mul(&s));
+        Ok(vec![Number::from_big(s), Number::from_big(r)])
+    });
+    interpreter.define_builtin("sqrt", 1, |interp, args| Ok(sqrt(&interp.from_sexpr(args[0])?)));
+    for &(name, f) in TRANSCENDENTAL {
+        interpreter.define_builtin(name, 1, move |interp, args| Ok(f(interp.from_sexpr(args[0])?)));
+    }
+    interpreter.define_builtin("log", Arity::Between(1, 2), |interp, args| {
+        let z: f64 = interp.from_sexpr(args[0])?;
+        match args.get(1) {
+            Some(&base) => Ok(z.ln() / interp.from_sexpr::<f64>(base)?.ln()),
+            None => Ok(z.ln()),
+        }
+    });
+    interpreter.define_builtin("atan", Arity::Between(1, 2), |interp, args| {
+        let y: f64 = interp.from_sexpr(args[0])?;
+        match args.get(1) {
+            Some(&x) => Ok(y.atan2(interp.from_sexpr(x)?)),
+            None => Ok(y.atan()),
+        }
+    });
+    for &(name, mode) in ROUNDING {
+        interpreter.define_builtin(name, 1, move |interp, args| Ok(round(&interp.from_sexpr(args[0])?, mode)));
+    }
+    interpreter.define_builtin("number->string", Arity::Between(1, 2), |interp, args| {
+        let n: Number = interp.from_sexpr(args[0])?;
+        match radix(interp, args.get(1))? {
+            10 => Ok(interp.to_string(args[0])),
+            _ if !n.is_exact() => Err("inexact numbers can only be written in radix 10"),
+            r => Ok(to_string_radix(&n, r)),
+        }
+    });
+    interpreter.define_builtin("string->number", Arity::Between(1, 2), |interp, args| {
+        let s: String = interp.from_sexpr(args[0])?;
+        let radix = radix(interp, args.get(1))?;
+        Ok(match parse(&s, radix) {
+            Some(n) => interp.to_sexpr(n)?,
+            None => SExpr::Bool(false),
+        })
+    });
+
+    interpreter.define_builtin("numerator", 1, |interp, args| {
+        let n: Number = interp.from_sexpr(args[0])?;
+        let num = Number::from_big(n.to_exact()?.parts().0);
+        Ok(if n.is_exact() { num } else { num.to_inexact() })
+    });
+    interpreter.define_builtin("denominator", 1, |interp, args| {
+        let n: Number = interp.from_sexpr(args[0])?;
+        let den = Number::from_big(n.to_exact()?.parts().1);
+        Ok(if n.is_exact() { den } else { den.to_inexact() })
+    });
+    for &name in &["exact", "inexact->exact"] {
+        interpreter.define_builtin(name, 1, |interp, args| interp.from_sexpr::<Number>(args[0])?.to_exact());
+    }
+    for &name in &["inexact", "exact->inexact"] {
+        interpreter.define_builtin(name, 1, |interp, args| Ok(interp.from_sexpr::<Number>(args[0])?.to_inexact()));
+    }
+}
+
+// Folds `f` over integer arguments; the result is inexact if any is.
+fn fold_integers(numbers: &[Number], initial: BigInt, f: fn(&BigInt, &BigInt) -> BigInt) -> Result<Number, &'static str> {
+    let mut acc = initial;
+    for n in numbers {
+        if !n.is_integer() {
+            return Err("type error: expected integer");
+        }
+        acc = f(&acc, &n.to_exact()?.parts().0);
+    }
+    let res = Number::from_big(acc);
+    Ok(if numbers.iter().all(Number::is_exact) { res } else { res.to_inexact() })
+}
+
+fn radix(interp: &Interpreter, e: Option<&SExpr>) -> Result<u32, &'static str> {
+    match e {
+        None => Ok(10),
+        Some(&e) => match interp.from_sexpr::<i64>(e)? {
+            r @ (2 | 8 | 10 | 16) => Ok(r as u32),
+            _ => Err("radix must be 2, 8, 10 or 16"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_promotion() {
+        assert_eq!(eval("(+ 9223372036854775807 1)"), "9223372036854775808");
+        assert_eq!(eval("(- -9223372036854775808 1)"), "-9223372036854775809");
+        assert_eq!(eval("(* 4294967296 4294967296)"), "18446744073709551616");
+        assert_eq!(eval("(- -9223372036854775808)"), "9223372036854775808");
+        assert_eq!(eval("(/ -9223372036854775808 -1)"), "9223372036854775808");
+        assert_eq!(eval("(define (fact n) (if (= n 0) 1 (* n (fact (- n 1))))) (fact 30)"),
+            "265252859812191058636308480000000");
+    }
+
+    #[test]
+    fn test_demotion() {
+        assert_eq!(eval("(- (+ 9223372036854775807 1) 1)"), "9223372036854775807");
+        assert_eq!(eval("(/ 100000000000000000000 10000000000)"), "10000000000");
+        assert_eq!(eval("(= (- 100000000000000000001 100000000000000000000) 1)"), "true");
+    }
+
+    #[test]
+    fn test_mixed() {
+        assert_eq!(eval("99999999999999999999"), "99999999999999999999");
+        assert_eq!(eval("-99999999999999999999"), "-99999999999999999999");
+        assert_eq!(eval("(+ 100000000000000000000 0.5)"), "1e20");
+        assert_eq!(eval("(< 99999999999999999999 100000000000000000000 1e30)"), "true");
+        assert_eq!(eval("(> -99999999999999999999 1)"), "false");
+        assert_eq!(eval("(/ 99999999999999999999 0)"), "division by zero");
+    }
+
+    #[test]
+    fn test_rationals() {
+        assert_eq!(eval("(/ 1 3)"), "1/3");
+        assert_eq!(eval("(/ 6 -4)"), "-3/2");
+        assert_eq!(eval("(/ 3)"), "1/3");
+        assert_eq!(eval("(+ 1/3 2/3)"), "1");
+        assert_eq!(eval("(* 2/3 3/4)"), "1/2");
+        assert_eq!(eval("(- 1/2 1/3)"), "1/6");
+        assert_eq!(eval("(/ 1/2 1/4)"), "2");
+        assert_eq!(eval("(/ 1/2 0)"), "division by zero");
+        assert_eq!(eval("-4/6"), "-2/3");
+        assert_eq!(eval("(/ 1 99999999999999999999)"), "1/99999999999999999999");
+        assert_eq!(eval("(< 1/3 0.34 1/2 1)"), "true");
+        assert_eq!(eval("(= 1/2 0.5)"), "true");
+        assert_eq!(eval("(> 1/3 1/3)"), "false");
+    }
+
+    #[test]
+    fn test_contagion() {
+        assert_eq!(eval("(+ 1/2 0.25)"), "0.75");
+        assert_eq!(eval("(* 1/2 4)"), "2");
+        assert_eq!(eval("(/ 1.0 4)"), "0.25");
+        assert_eq!(eval("(* 1/3 3.0)"), "1.0");
+    }
+
+    #[test]
+    fn test_exactness() {
+        assert_eq!(eval("(numerator 6/4)"), "3");
+        assert_eq!(eval("(denominator 6/4)"), "2");
+        assert_eq!(eval("(denominator 5)"), "1");
+        assert_eq!(eval("(denominator 0.75)"), "4.0");
+        assert_eq!(eval("(exact 0.75)"), "3/4");
+        assert_eq!(eval("(exact 1e20)"), "100000000000000000000");
+        assert_eq!(eval("(exact -2.0)"), "-2");
+        assert_eq!(eval("(inexact 1/4)"), "0.25");
+        assert_eq!(eval("(exact (/ 1.0 0))"), "no exact representation");
+        assert_eq!(eval("(exact->inexact 1/2)"), "0.5");
+    }
+
+    #[test]
+    fn test_integer_division() {
+        assert_eq!(eval("(list (quotient 7 2) (remainder 7 2) (modulo 7 2))"), "(3 1 1)");
+        assert_eq!(eval("(list (quotient -7 2) (remainder -7 2) (modulo -7 2))"), "(-3 -1 1)");
+        assert_eq!(eval("(list (modulo 7 -2) (modulo -7 -2))"), "(-1 -1)");
+        assert_eq!(eval("(floor/ -7 2)"), "(-4 1)");
+        assert_eq!(eval("(truncate/ -7 2)"), "(-3 -1)");
+        assert_eq!(eval("(floor-quotient 7 -2)"), "-4");
+        assert_eq!(eval("(modulo -7.0 2)"), "1.0");
+        assert_eq!(eval("(quotient 100000000000000000000 -3)"), "-33333333333333333333");
+        assert_eq!(eval("(modulo -100000000000000000000 3)"), "2");
+        assert_eq!(eval("(quotient -9223372036854775808 -1)"), "9223372036854775808");
+        assert_eq!(eval("(quotient 1 0)"), "division by zero");
+        assert_eq!(eval("(modulo 1.0 0)"), "division by zero");
+        assert_eq!(eval("(remainder 1/2 2)"), "type error: expected integer");
+    }
+
+    #[test]
+    fn test_library() {
+        assert_eq!(eval("(list (abs -5) (abs 5/3) (abs -1/2) (abs -2.5))"), "(5 5/3 1/2 2.5)");
+        assert_eq!(eval("(list (min 3 1 2) (max 1/2 1/3) (max 1 2.0))"), "(1 1/2 2.0)");
+        assert_eq!(eval("(inexact? (max 3 2.0))"), "true");
+        assert_eq!(eval("(list (gcd 12 -18) (gcd) (lcm 4 6) (lcm) (lcm 3 0))"), "(6 0 12 1 0)");
+        assert_eq!(eval("(list (expt 2 10) (expt 2 -2) (expt 2/3 3) (expt 0 0) (expt 4 0.5))"), "(1024 1/4 8/27 1 2.0)");
+        assert_eq!(eval("(expt 2 100)"), "1267650600228229401496703205376");
+        assert_eq!(eval("(expt -1 100000000000000000000)"), "1");
+        assert_eq!(eval("(expt 0 -1)"), "division by zero");
+        assert_eq!(eval("(expt 2 100000000000)"), "exponent too large");
+        assert_eq!(eval("(exact-integer-sqrt 17)"), "(4 1)");
+        assert_eq!(eval("(exact-integer-sqrt -1)"), "type error: expected exact non-negative integer");
+        assert_eq!(eval("(list (sqrt 16) (sqrt 1/4) (sqrt 2.25))"), "(4 1/2 1.5)");
+        assert_eq!(eval("(sqrt 2)"), "1.4142135623730951");
+        assert_eq!(eval("(list (exp 0) (log 1) (log 8 2) (sin 0) (atan 1 1))"), "(1.0 0.0 3.0 0.0 0.7853981633974483)");
+    }
+
+    #[test]
+    fn test_rounding() {
+        assert_eq!(eval("(list (floor -7/2) (ceiling -7/2) (round -7/2) (truncate -7/2))"), "(-4 -3 -4 -3)");
+        assert_eq!(eval("(list (round 5/2) (round 7/2) (round 2.5) (round -2.5) (round 2.6))"), "(2 4 2.0 -2.0 3.0)");
+        assert_eq!(eval("(list (floor 2.5) (ceiling 2.1) (truncate -2.7) (floor 3))"), "(2.0 3.0 -2.0 3)");
+        assert_eq!(eval("(exact? (floor 2.5))"), "false");
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert_eq!(eval("(list (number? 1) (number? 1/2) (number? \"1\") (integer? 2.0) (integer? 1/2))"), "(true true false true false)");
+        assert_eq!(eval("(list (rational? 0.5) (rational? (/ 1.0 0)) (exact-integer? 5) (exact-integer? 5.0))"), "(true false true false)");
+        assert_eq!(eval("(list (zero? 0) (zero? 0.0) (positive? 1/2) (negative? -1) (positive? 0))"), "(true true true true false)");
+        assert_eq!(eval("(list (odd? 3) (even? 3) (even? -4) (odd? 100000000000000000001) (even? 2.0))"), "(true false true true true)");
+        assert_eq!(eval("(odd? 1/2)"), "type error: expected integer");
+        assert_eq!(eval("(zero? (quote a))"), "type error: expected number");
+        assert_eq!(eval("(list (nan? (/ 0.0 0)) (infinite? (/ -1.0 0)) (finite? 1/3))"), "(true true true)");
+    }
+
+    #[test]
+    fn test_number_strings() {
+        assert_eq!(eval("(number->string 255 16)"), "\"ff\"");
+        assert_eq!(eval("(number->string -10 2)"), "\"-1010\"");
+        assert_eq!(eval("(number->string 1/3 2)"), "\"1/11\"");
+        assert_eq!(eval("(number->string 1.5)"), "\"1.5\"");
+        assert_eq!(eval("(number->string 1.5 2)"), "inexact numbers can only be written in radix 10");
+        assert_eq!(eval("(number->string 1 3)"), "radix must be 2, 8, 10 or 16");
+        assert_eq!(eval("(string->number \"ff\" 16)"), "255");
+        assert_eq!(eval("(string->number \"-2/4\")"), "-1/2");
+        assert_eq!(eval("(string->number \"1e3\")"), "1000.0");
+        assert_eq!(eval("(string->number \"100000000000000000000\")"), "100000000000000000000");
+        assert_eq!(eval("(list (string->number \"abc\") (string->number \"1/0\") (string->number \"inf\"))"), "(false false false)");
+        assert_eq!(eval("(string->number \"12\" 2)"), "false");
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(eval("(list #x1F #X-ff #b1010 #o17 #d10 #x1/A)"), "(31 -255 10 15 10 1/10)");
+        assert_eq!(eval("(list #e1.5 #e1e-2 #i3 #i1/4 #e#x10 #x#e10)"), "(3/2 1/100 3.0 0.25 16 16)");
+        assert_eq!(eval("#e1.2e400"), format!("12{}", "0".repeat(399)));
+        assert_eq!(eval("(list +inf.0 -inf.0 +nan.0 (- +inf.0))"), "(+inf.0 -inf.0 +nan.0 -inf.0)");
+        assert_eq!(eval("(list (string->number \"#xff\") (string->number \"#e0.5\") (string->number \"ff\" 16))"), "(255 1/2 255)");
+        assert_eq!(eval("(string->number \"#x#b1\")"), "false");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut interpreter = Interpreter::new(1000);
+        let values = "(list 1.0 -0.0 0.1 1e20 1.5e-7 123456.789 (/ 1.0 3) +inf.0 -inf.0 \
+                      1/3 -7/2 99999999999999999999 (- 1/99999999999999999999))";
+        let printed = interpreter.eval_string(values).map(|res| interpreter.to_string(res)).unwrap();
+        let reread = interpreter.eval_string(&format!("(quote {})", printed)).unwrap();
+        assert_eq!(interpreter.to_string(reread), printed);
+        let same = interpreter.eval_string(&format!("(equal? (quote {}) {})", printed, values)).unwrap();
+        assert_eq!(interpreter.to_string(same), "true");
+    }
+}
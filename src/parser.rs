@@ -1,18 +1,18 @@
-use crate::sexpr::{SExpr, Memory};
-use crate::scanner::{Token, ParseError};
+use crate::sexpr::{SExpr, Memory, Object};
+use crate::scanner::{self, Token, ParseError};
+use crate::numbers;
+use crate::convert::ToSExpr;
 
 pub struct Parser<'s, 'm> {
     tokens: Vec<Token<'s>>,
-    start: usize,
     current: usize,
-    mem: &'m mut Memory<'s>,
+    mem: &'m mut Memory,
 }
 
 impl<'s, 'm> Parser<'s, 'm> {
-    pub fn new(tokens: Vec<Token<'s>>, mem: &'m mut Memory<'s>) -> Self {
+    pub fn new(tokens: Vec<Token<'s>>, mem: &'m mut Memory) -> Self {
         Parser {
             tokens,
-            start: 0,
             current: 0,
             mem,
         }
@@ -20,43 +20,68 @@ impl<'s, 'm> Parser<'s, 'm> {
 
     // sexpr ::= int | float | symbol | string | '(' ')' | '(' sexpr (sexpr)* ')'
 
-    pub fn parse(mut self) -> Result<SExpr<'s>, ParseError> {
+    #[cfg(test)]
+    pub fn parse(mut self) -> Result<SExpr, ParseError> {
         self.expr()
     }
 
-    fn expr(&mut self) -> Result<SExpr<'s>, ParseError> {
+    pub fn parse_all(mut self) -> Result<Vec<SExpr>, ParseError> {
+        let mut exprs = Vec::new();
+        while !self.at_end() {
+            exprs.push(self.expr()?);
+        }
+        Ok(exprs)
+    }
+
+    fn expr(&mut self) -> Result<SExpr, ParseError> {
         match self.advance() {
             None => Err(ParseError { message: "Empty expression", line: 0 }),
             Some(token) => match token {
                 Token::Int(x) => Ok(SExpr::Int(x)),
+                Token::Number(x) => match numbers::parse(x, 10) {
+                    Some(n) => Ok(n.to_sexpr(self.mem)),
+                    None => Err(ParseError { message: "invalid number", line: 0 }),
+                }
                 Token::Float(x) => Ok(SExpr::Float(x)),
-                Token::Str(x) => Ok(SExpr::Str(x)),
-                Token::Symbol(x) => Ok(SExpr::Sym(x)),
+                Token::Str(x) => Ok(self.mem.alloc(Object::Str(scanner::unescape(x)))),
+                Token::Symbol(x) => Ok(SExpr::Sym(self.mem.intern(x))),
                 Token::Bool(x) => Ok(SExpr::Bool(x)),
-                Token::OpenParen => match self.peek() {
-                    None => Err(ParseError { message: "Missing closing parenthesis", line: 0}),
-                    Some(Token::ClosedParen) => { self.advance(); Ok(SExpr::Nil) },
-                    _ => {
-                        let mut exprs = Vec::new();
-                        loop {
-                            let previous = self.current;
-                            match self.expr() {
-                                Ok(e) => exprs.push(e), 
-                                Err(_) => { self.current = previous; break }
-                            }
-                        }
-                        // println!("{:?}", exprs);
-                        match self.advance() {
-                            Some(Token::ClosedParen) => Ok(self.mem.list_from_vec(exprs)), // unimplemented!(), //Ok(SExpr::from_vec(self.mem, exprs).unwrap()),
-                            _ => Err(ParseError { message: "Missing closing parenthesis", line: 0}),
+                Token::Char(x) => Ok(SExpr::Char(x)),
+                Token::OpenParen => {
+                    let exprs = self.elements()?;
+                    Ok(self.mem.list_from_vec(exprs))
+                }
+                Token::OpenVector => {
+                    let exprs = self.elements()?;
+                    Ok(self.mem.alloc(Object::Vector(exprs)))
+                }
+                Token::OpenBytevector => {
+                    let mut bytes = Vec::new();
+                    for e in self.elements()? {
+                        match e {
+                            SExpr::Int(b @ 0..=255) => bytes.push(b as u8),
+                            _ => return Err(ParseError { message: "Bytevector elements must be bytes", line: 0 }),
                         }
                     }
+                    Ok(self.mem.alloc(Object::Bytevector(bytes)))
                 }
                 Token::ClosedParen => Err(ParseError { message: "Unexpected closing parenthesis", line: 0}),
             }
         }
     }    
 
+    // The expressions up to and including the closing parenthesis.
+    fn elements(&mut self) -> Result<Vec<SExpr>, ParseError> {
+        let mut exprs = Vec::new();
+        loop {
+            match self.peek() {
+                None => return Err(ParseError { message: "Missing closing parenthesis", line: 0 }),
+                Some(Token::ClosedParen) => { self.advance(); return Ok(exprs) }
+                _ => exprs.push(self.expr()?),
+            }
+        }
+    }
+
     fn advance(&mut self) -> Option<Token<'s>> {
         match self.tokens.get(self.current) {
             Some(&token) => {
@@ -68,10 +93,7 @@ impl<'s, 'm> Parser<'s, 'm> {
     }
 
     fn peek(&self) -> Option<Token<'s>> {
-        match self.tokens.get(self.current) {
-            Some(&token) => Some(token),
-            None => None,
-        }
+        self.tokens.get(self.current).copied()
     }
 
     fn at_end(&self) -> bool {
@@ -93,12 +115,12 @@ mod tests {
                 let parser = Parser::new(tokens, &mut mem);
                 let res = parser.parse();
                 assert!(res.is_ok());
-                assert_eq!(res.unwrap(), $right);
+                assert_eq!(mem.to_string(res.unwrap()), $right);
             })*
         };
     }
 
-    // fn do_parse<'s>(s: &'static str) -> (SExpr<'s>, Mem$mem$mem$memory<'s, )
+    // fn do_parse<'s>(s: &'static str) -> (SExpr, Mem$mem$mem$memory<'s, )
 
     // fn parse_err<'a>(s: &'a str) {
     //     let scanner = Scanner::new(s);
@@ -109,26 +131,43 @@ mod tests {
     //     assert!(res.is_err());
     // }
 
-    fn f<'s>(x: f64) -> SExpr<'s> { SExpr::Float(x) }
-    fn i<'s>(x: i64) -> SExpr<'s> { SExpr::Int(x) }
-    fn b<'s>(x: bool) -> SExpr<'s> { SExpr::Bool(x) }
-    fn sy<'s>(x: &'s str) -> SExpr<'s> { SExpr::Sym(x) }
-    fn st<'s>(x: &'s str) -> SExpr<'s> { SExpr::Str(x) }
-    fn n<'s>() -> SExpr<'s> { SExpr::Nil }
+    fn i(x: i64) -> SExpr { SExpr::Int(x) }
+    fn sy(x: &str, mem: &mut Memory) -> SExpr { SExpr::Sym(mem.intern(x)) }
 
     #[test]
     fn test_values() {
         parse_ok![
-            ("123", i(123)),
-            ("1.0", f(1.0)),
-            ("abc", sy("abc")),
-            ("\"abc\"", st("abc")),
-            ("()", n()),
-            ("#t", b(true)),
-            ("#f", b(false))
+            ("123", "123"),
+            ("1.0", "1.0"),
+            ("abc", "abc"),
+            ("\"abc\"", "\"abc\""),
+            ("()", "()"),
+            ("#t", "true"),
+            ("#f", "false"),
+            ("#\\a", "#\\a"),
+            ("#\\space", "#\\space"),
+            ("#(1 (2) #())", "#(1 (2) #())"),
+            ("#u8(0 255)", "#u8(0 255)")
         ];
     }
 
+    #[test]
+    fn test_parse_all() {
+        let mut mem = Memory::new(100);
+        let tokens = Scanner::new("(define a 1) a 2").scan_tokens().unwrap();
+        let exprs = Parser::new(tokens, &mut mem).parse_all().unwrap();
+        assert_eq!(exprs.len(), 3);
+        assert_eq!(exprs[1], sy("a", &mut mem));
+        assert_eq!(exprs[2], i(2));
+        let tokens = Scanner::new("(f 1)) 2").scan_tokens().unwrap();
+        assert_eq!(Parser::new(tokens, &mut mem).parse_all().unwrap_err().message, "Unexpected closing parenthesis");
+
+        let tokens = Scanner::new("1 (").scan_tokens().unwrap();
+        assert!(Parser::new(tokens, &mut mem).parse_all().is_err());
+        let tokens = Scanner::new("#u8(1 256)").scan_tokens().unwrap();
+        assert!(Parser::new(tokens, &mut mem).parse_all().is_err());
+    }
+
     #[test]
     fn test_exprs() {
         let mut mem = Memory::new(100);
@@ -136,7 +175,8 @@ mod tests {
         let tokens = scanner.scan_tokens().unwrap();
         let parser = Parser::new(tokens, &mut mem);
         let res = parser.parse();
-        assert_eq!(mem.vec_from_list(res.unwrap()).unwrap(), vec![sy("+")]);
+        let plus = sy("+", &mut mem);
+        assert_eq!(mem.vec_from_list(res.unwrap()).unwrap(), vec![plus]);
         // assert_eq!(mem.vec_from_list(res).unwrap(), vec![sy("+")]);
         // {
         //     parse!("(+)", x, mem);
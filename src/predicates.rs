@@ -0,0 +1,80 @@
+use crate::interpreter::Interpreter;
+use crate::sexpr::{SExpr, Memory, Object};
+use crate::symbol::Symbol;
+
+// Type predicates for the kinds of value that have no library module of
+// their own, and the conversions between symbols and strings.
+
+/// Whether `e` is a proper list. Cyclic lists are not, and are detected by
+/// walking the list at two speeds.
+pub fn is_list(mem: &Memory, e: SExpr) -> bool {
+    let (mut slow, mut fast) = (e, e);
+    loop {
+        for _ in 0..2 {
+            if fast == SExpr::Nil {
+                return true;
+            }
+            match mem.cdr(fast) {
+                Ok(next) => fast = next,
+                Err(_) => return false,
+            }
+        }
+        slow = mem.cdr(slow).unwrap();
+        if slow == fast {
+            return false;
+        }
+    }
+}
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("pair?", 1, |interp, args| {
+        Ok(matches!(interp.memory().deref(args[0]), Some(Object::Pair(_, _))))
+    });
+    interpreter.define_builtin("list?", 1, |interp, args| Ok(is_list(interp.memory(), args[0])));
+    interpreter.define_builtin("symbol?", 1, |_, args| Ok(matches!(args[0], SExpr::Sym(_))));
+    interpreter.define_builtin("boolean?", 1, |_, args| Ok(matches!(args[0], SExpr::Bool(_))));
+    interpreter.define_builtin("procedure?", 1, |interp, args| Ok(interp.is_procedure(args[0])));
+    interpreter.define_builtin("eof-object?", 1, |_, args| Ok(args[0] == SExpr::Eof));
+    interpreter.define_builtin("eof-object", 0, |_, _| Ok(SExpr::Eof));
+    interpreter.define_builtin("symbol->string", 1, |interp, args| {
+        let sym: Symbol = interp.from_sexpr(args[0])?;
+        Ok(interp.symbol_name(sym).to_string())
+    });
+    interpreter.define_builtin("string->symbol", 1, |interp, args| {
+        let name: String = interp.from_sexpr(args[0])?;
+        Ok(interp.intern(&name))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_predicates() {
+        assert_eq!(eval("(list (pair? (cons 1 2)) (pair? (list)) (pair? #(1)))"), "(true false false)");
+        assert_eq!(eval("(list (symbol? (quote a)) (symbol? \"a\") (boolean? #f) (boolean? 0))"), "(true false true false)");
+        assert_eq!(eval("(list (procedure? car) (procedure? (lambda (x) x)) (procedure? string-length))"), "(true true true)");
+        assert_eq!(eval("(list (procedure? (quote car)) (procedure? 1))"), "(false false)");
+        assert_eq!(eval("(list (eof-object? (eof-object)) (eof-object? (list)))"), "(true false)");
+        assert_eq!(eval("(eof-object)"), "#<eof>");
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(eval("(list (list? (list)) (list? (list 1 2 3)) (list? (cons 1 2)) (list? 1))"), "(true true false false)");
+        assert_eq!(eval("(define x (list 1 2 3)) (set-cdr! (cdr (cdr x)) x) (list? x)"), "false");
+        assert_eq!(eval("(define x (list 1)) (set-cdr! x x) (list? x)"), "false");
+        assert_eq!(eval("(define x (list 1 2)) (set-cdr! (cdr x) (cdr x)) (list? x)"), "false");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(eval("(symbol->string (quote abc))"), "\"abc\"");
+        assert_eq!(eval("(string->symbol \"hello world\")"), "hello world");
+        assert_eq!(eval("(eq? (string->symbol \"abc\") (quote abc))"), "true");
+        assert_eq!(eval("(symbol->string \"abc\")"), "type error: expected symbol");
+        assert_eq!(eval("(list->string (list #\\a #\\b))"), "\"ab\"");
+        assert_eq!(eval("(number->string 42)"), "\"42\"");
+    }
+}
@@ -0,0 +1,152 @@
+use crate::interpreter::Interpreter;
+use crate::sexpr::{SExpr, Memory, Object};
+
+// R7RS promises. Forcing follows the reference implementation: a promise
+// whose `delay-force` body yields another promise takes over that promise's
+// state, so chains of `delay-force` are forced in a loop, in constant space.
+
+/// The state of a promise made by `delay`, `delay-force` or `make-promise`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Promise {
+    Forced(SExpr),
+    /// `expr` is still to be evaluated in `env`. With `delay-force` it is
+    /// `lazy` and evaluates to another promise.
+    Delayed { expr: SExpr, env: SExpr, lazy: bool },
+    /// Shares the state of the promise it refers to.
+    Shared(SExpr),
+}
+
+/// The value of the promise `e`, computing it if needed. Anything that is
+/// not a promise is its own value. Garbage may be collected while forcing,
+/// so everything else the caller still needs must be rooted.
+pub fn force(interp: &mut Interpreter, e: SExpr) -> Result<SExpr, &'static str> {
+    if root(interp.memory(), e).is_none() {
+        return Ok(e);
+    }
+    let slot = interp.root(e);
+    let res = force_rooted(interp, e);
+    interp.unroot(slot);
+    res
+}
+
+fn force_rooted(interp: &mut Interpreter, e: SExpr) -> Result<SExpr, &'static str> {
+    loop {
+        let p = root(interp.memory(), e).unwrap();
+        let (expr, env, lazy) = match state(interp.memory(), p) {
+            Promise::Forced(value) => return Ok(value),
+            Promise::Delayed { expr, env, lazy } => (expr, env, lazy),
+            Promise::Shared(_) => unreachable!(),
+        };
+        let value = interp.eval_in_rooted(expr, env)?;
+        // forcing the body may have forced this promise already
+        let p = root(interp.memory(), e).unwrap();
+        if let Promise::Forced(_) = state(interp.memory(), p) {
+            continue;
+        }
+        if !lazy {
+            set_state(interp.memory_mut(), p, Promise::Forced(value));
+            continue;
+        }
+        let q = root(interp.memory(), value).ok_or("type error: expected promise")?;
+        if q == p {
+            return Err("promise forces itself");
+        }
+        let next = state(interp.memory(), q);
+        set_state(interp.memory_mut(), p, next);
+        if let Promise::Delayed { .. } = next {
+            set_state(interp.memory_mut(), q, Promise::Shared(p));
+        }
+    }
+}
+
+// The promise holding the state of `e`, if `e` is a promise.
+fn root(mem: &Memory, mut e: SExpr) -> Option<SExpr> {
+    loop {
+        match mem.deref(e) {
+            Some(&Object::Promise(Promise::Shared(next))) => e = next,
+            Some(Object::Promise(_)) => return Some(e),
+            _ => return None,
+        }
+    }
+}
+
+fn state(mem: &Memory, p: SExpr) -> Promise {
+    match mem.deref(p) {
+        Some(&Object::Promise(promise)) => promise,
+        _ => unreachable!(),
+    }
+}
+
+fn set_state(mem: &mut Memory, p: SExpr, promise: Promise) {
+    if let SExpr::Ref(addr) = p {
+        *mem.get_mut(addr) = Object::Promise(promise);
+    }
+}
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("force", 1, |interp, args| force(interp, args[0]));
+    interpreter.define_builtin("make-promise", 1, |interp, args| {
+        if root(interp.memory(), args[0]).is_some() {
+            return Ok(args[0]);
+        }
+        Ok(interp.memory_mut().alloc(Object::Promise(Promise::Forced(args[0]))))
+    });
+    interpreter.define_builtin("promise?", 1, |interp, args| Ok(root(interp.memory(), args[0]).is_some()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::eval_to_string as eval;
+
+    #[test]
+    fn test_delay() {
+        assert_eq!(eval("(force (delay (+ 1 2)))"), "3");
+        assert_eq!(eval("(let ((p (delay (+ 1 2)))) (list (force p) (force p)))"), "(3 3)");
+        assert_eq!(eval("(define n 0) (define p (delay (begin (set! n (+ n 1)) n))) (force p) (force p) n"), "1");
+        assert_eq!(eval("(define n 0) (delay (set! n 1)) n"), "0");
+        assert_eq!(eval("(force 5)"), "5");
+        assert_eq!(eval("(delay 1)"), "#<promise>");
+        assert_eq!(eval("(list (promise? (delay 1)) (promise? (make-promise 1)) (promise? 1))"), "(true true false)");
+        assert_eq!(eval("(force (make-promise (list 1)))"), "(1)");
+        assert_eq!(eval("(define p (delay 1)) (eq? p (make-promise p))"), "true");
+        assert_eq!(eval("(force (delay (car 1)))"), "type error");
+        assert_eq!(eval("(delay 1 2)"), "ill formed delay");
+    }
+
+    #[test]
+    fn test_reentrant() {
+        // from R7RS section 4.2.5
+        assert_eq!(eval("(define x 5) (define count 0) \
+                         (define p (delay (begin (set! count (+ count 1)) (if (> count x) count (force p))))) \
+                         (list (force p) (begin (set! x 10) (force p)))"), "(6 6)");
+    }
+
+    #[test]
+    fn test_delay_force() {
+        assert_eq!(eval("(force (delay-force (delay 7)))"), "7");
+        assert_eq!(eval("(define (loop n) (delay-force (if (= n 0) (delay (quote done)) (loop (- n 1))))) \
+                         (force (loop 50))"), "done");
+        assert_eq!(eval("(define (loop n) (delay-force (if (= n 0) (delay (quote done)) (loop (- n 1))))) \
+                         (define p (loop 3)) (force p) (force p)"), "done");
+        assert_eq!(eval("(force (delay-force 1))"), "type error: expected promise");
+        assert_eq!(eval("(define p (delay-force p)) (force p)"), "promise forces itself");
+    }
+
+    #[test]
+    fn test_constant_space() {
+        let mut interpreter = Interpreter::default();
+        let res = interpreter.eval_string("(define (loop n) (delay-force (if (= n 0) (delay n) (loop (- n 1))))) \
+                                           (force (loop 10000))").unwrap();
+        assert_eq!(interpreter.to_string(res), "0");
+    }
+
+    #[test]
+    fn test_streams() {
+        let integers = "(define (integers-from n) (cons-stream n (integers-from (+ n 1)))) \
+                        (define (take s k) (if (= k 0) (list) (cons (car s) (take (force (cdr s)) (- k 1)))))";
+        assert_eq!(eval(&format!("{} (take (integers-from 1) 5)", integers)), "(1 2 3 4 5)");
+        assert_eq!(eval("(define s (cons-stream 1 (car 1))) (car s)"), "1");
+        assert_eq!(eval("(cons-stream 1)"), "ill formed cons-stream");
+    }
+}
@@ -0,0 +1,114 @@
+use crate::sexpr::{SExpr, Memory, Object};
+use crate::symbol::Symbol;
+
+/// A record type descriptor created by `define-record-type`.
+#[derive(PartialEq, Debug)]
+pub struct RecordType {
+    pub name: String,
+    pub fields: Vec<Symbol>,
+}
+
+impl RecordType {
+    /// The name without the conventional angle brackets, as in `<point>`.
+    pub fn display_name(&self) -> &str {
+        self.name.trim_start_matches('<').trim_end_matches('>')
+    }
+}
+
+/// An instance of a record type; `rtype` refers to its `Object::RecordType`.
+#[derive(PartialEq, Debug)]
+pub struct Record {
+    pub rtype: SExpr,
+    pub fields: Vec<SExpr>,
+}
+
+/// The procedures `define-record-type` defines for a record type.
+#[derive(PartialEq, Debug, Clone)]
+pub enum RecordProcedure {
+    /// Takes one argument per listed field slot.
+    Constructor(SExpr, Vec<usize>),
+    Predicate(SExpr),
+    Accessor(SExpr, usize),
+    Modifier(SExpr, usize),
+}
+
+pub fn apply(mem: &mut Memory, procd: &RecordProcedure, args: &[SExpr]) -> Result<SExpr, &'static str> {
+    match *procd {
+        RecordProcedure::Constructor(rtype, ref slots) => {
+            if args.len() != slots.len() {
+                return Err("wrong arity");
+            }
+            let len = match mem.deref(rtype) {
+                Some(Object::RecordType(t)) => t.fields.len(),
+                _ => return Err("type error: expected record type"),
+            };
+            let mut fields = vec![SExpr::Nil; len];
+            for (&slot, &arg) in slots.iter().zip(args) {
+                fields[slot] = arg;
+            }
+            Ok(mem.alloc(Object::Record(Record { rtype, fields })))
+        }
+        RecordProcedure::Predicate(rtype) => match *args {
+            [e] => Ok(SExpr::Bool(matches!(mem.deref(e), Some(Object::Record(r)) if r.rtype == rtype))),
+            _ => Err("wrong arity"),
+        },
+        RecordProcedure::Accessor(rtype, slot) => match *args {
+            [e] => Ok(record_mut(mem, e, rtype)?.fields[slot]),
+            _ => Err("wrong arity"),
+        },
+        RecordProcedure::Modifier(rtype, slot) => match *args {
+            [e, value] => {
+                record_mut(mem, e, rtype)?.fields[slot] = value;
+                Ok(SExpr::Nil)
+            }
+            _ => Err("wrong arity"),
+        },
+    }
+}
+
+fn record_mut(mem: &mut Memory, e: SExpr, rtype: SExpr) -> Result<&mut Record, &'static str> {
+    match e {
+        SExpr::Ref(addr) => match mem.get_mut(addr) {
+            Object::Record(r) if r.rtype == rtype => Ok(r),
+            _ => Err("type error: wrong record type"),
+        },
+        _ => Err("type error: wrong record type"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::Interpreter;
+
+    fn eval(s: &str) -> String {
+        let mut interpreter = Interpreter::new(1000);
+        let point = "(define-record-type <point> (make-point x y) point? (x point-x set-point-x!) (y point-y))";
+        interpreter.eval_string(point).unwrap();
+        match interpreter.eval_string(s) {
+            Ok(res) => interpreter.to_string(res),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_records() {
+        assert_eq!(eval("(make-point 1 2)"), "#<record point x: 1 y: 2>");
+        assert_eq!(eval("(point-y (make-point 1 2))"), "2");
+        assert_eq!(eval("(define p (make-point 1 2)) (set-point-x! p 5) (point-x p)"), "5");
+        assert_eq!(eval("(point? (make-point 1 2))"), "true");
+        assert_eq!(eval("(point? (cons 1 2))"), "false");
+        assert_eq!(eval("<point>"), "#<record-type point>");
+        assert_eq!(eval("(make-point 1)"), "wrong arity");
+        assert_eq!(eval("(point-x (cons 1 2))"), "type error: wrong record type");
+    }
+
+    #[test]
+    fn test_distinct_types() {
+        assert_eq!(eval("(define-record-type other (make-other x) other? (x other-x)) \
+                         (list (point? (make-other 1)) (other? (make-other 1)))"), "(false true)");
+        assert_eq!(eval("(define-record-type cell (make-cell) cell? (v cell-v set-cell-v!)) \
+                         (define c (make-cell)) (set-cell-v! c 3) c"), "#<record cell v: 3>");
+        assert_eq!(eval("(define-record-type bad (make-bad z) bad? (x bad-x))"), "unknown field in record constructor");
+        assert_eq!(eval("(define-record-type bad (make-bad) bad?)"), "bad");
+    }
+}
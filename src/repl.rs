@@ -0,0 +1,312 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use rscheme::{Interpreter, SExpr, Object, Promise, SPECIAL_FORMS};
+use rscheme::{Scanner, Token, MISSING_QUOTE};
+
+use crate::editor::Editor;
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = ". ";
+const INDENT_WIDTH: usize = 2;
+
+fn history_file() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".rscheme_history"))
+}
+
+/// Number of parens left open in `source`, or `None` if it does not scan
+/// or closes a paren it did not open.
+fn open_parens(source: &str) -> Option<usize> {
+    let tokens = Scanner::new(source).scan_tokens().ok()?;
+    let mut depth = 0usize;
+    for token in tokens {
+        match token {
+            Token::OpenParen | Token::OpenVector | Token::OpenBytevector => depth += 1,
+            Token::ClosedParen => depth = depth.checked_sub(1)?,
+            _ => (),
+        }
+    }
+    Some(depth)
+}
+
+/// Whether `source` ends inside a string literal.
+fn in_string(source: &str) -> bool {
+    matches!(Scanner::new(source).scan_tokens(), Err(e) if e.message == MISSING_QUOTE)
+}
+
+/// Bound identifiers and special form keywords starting with `prefix`.
+fn completions(interpreter: &Interpreter, prefix: &str) -> Vec<String> {
+    let mut candidates: Vec<String> = interpreter.bound_names().into_iter()
+        .chain(SPECIAL_FORMS.iter().copied())
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// Reads lines until the parens balance and strings are closed. Returns
+/// `Ok(None)` at end of input.
+fn read_input(editor: &mut Editor, interpreter: &Interpreter) -> io::Result<Option<String>> {
+    let complete = |prefix: &str| completions(interpreter, prefix);
+    let mut source = String::new();
+    let mut prompt = PROMPT;
+    let mut indent = String::new();
+    loop {
+        let line = match editor.read_line(prompt, &indent, &complete) {
+            Ok(Some(line)) => line,
+            Ok(None) => return Ok(None),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {
+                source.clear();
+                prompt = PROMPT;
+                indent.clear();
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        source.push_str(&line);
+        source.push('\n');
+        match open_parens(&source) {
+            Some(depth) if depth > 0 => {
+                prompt = CONTINUATION_PROMPT;
+                indent = " ".repeat(depth * INDENT_WIDTH);
+            }
+            // indenting would add spaces to the string
+            None if in_string(&source) => {
+                prompt = CONTINUATION_PROMPT;
+                indent.clear();
+            }
+            _ => return Ok(Some(source)),
+        }
+    }
+}
+
+const HELP: &str = "\
+,time expr      evaluate expr and report elapsed time and allocations
+,expand form    show form with derived forms rewritten to core forms
+,describe expr  show what kind of object expr evaluates to
+,load file      evaluate every expression in file
+,reset          start over with a fresh interpreter
+,help           show this message";
+
+/// Runs `f` on the interpreter, turning a panic inside it into an error so
+/// a bug in the interpreter does not end the session. Running out of memory
+/// is an ordinary error and does not get here.
+fn guarded<T>(interpreter: &mut Interpreter, f: impl FnOnce(&mut Interpreter) -> Result<T, &'static str>)
+    -> Result<T, &'static str>
+{
+    match panic::catch_unwind(AssertUnwindSafe(|| f(interpreter))) {
+        Ok(res) => res,
+        Err(_) => Err("internal interpreter error (this is a bug)"),
+    }
+}
+
+fn describe(interpreter: &Interpreter, e: SExpr) -> String {
+    let mem = interpreter.memory();
+    match e {
+        SExpr::Nil => "the empty list".to_string(),
+        SExpr::Eof => "the end-of-file object".to_string(),
+        SExpr::Unspecified => "the unspecified value".to_string(),
+        SExpr::Int(i) => format!("the integer {}", i),
+        SExpr::Float(f) => format!("the float {}", f),
+        SExpr::Bool(b) => format!("the boolean {}", interpreter.to_string(SExpr::Bool(b))),
+        SExpr::Char(c) => format!("the character {}", interpreter.to_string(SExpr::Char(c))),
+        SExpr::Sym(s) => format!("the symbol {}", interpreter.symbol_name(s)),
+        SExpr::Ref(addr) => match *mem.get(addr) {
+            Object::Pair(_, _) => match mem.vec_from_list(e) {
+                Ok(vec) => format!("a list of length {}", vec.len()),
+                Err(_) => "a pair".to_string(),
+            }
+            Object::BigInt(ref b) => format!("the integer {}", b),
+            Object::Rational(ref r) => format!("the rational number {}", r),
+            Object::PrimitiveProcedure(procd) => format!("a primitive procedure ({:?})", procd),
+            Object::CompoundProcedure(procd) => match mem.car(procd) {
+                Ok(params) => format!("a compound procedure with parameters {}", interpreter.to_string(params)),
+                Err(_) => "a compound procedure".to_string(),
+            }
+            Object::NativeProcedure(ref native) => format!("a native procedure ({:?})", native.arity),
+            Object::Str(ref s) => format!("a string of length {}", s.len()),
+            Object::Vector(ref v) => format!("a vector of length {}", v.len()),
+            Object::Bytevector(ref bytes) => format!("a bytevector of length {}", bytes.len()),
+            Object::HashTable(ref table) => format!("a hash table with {} entries", table.len()),
+            Object::RecordType(ref t) => format!("a record type with fields {}",
+                t.fields.iter().map(|&f| interpreter.symbol_name(f)).collect::<Vec<_>>().join(" ")),
+            Object::Record(_) => "a record".to_string(),
+            Object::RecordProcedure(ref procd) => format!("a record procedure ({:?})", procd),
+            Object::Host(ref host) => format!("a host object of type {}", host.type_name()),
+            Object::Promise(Promise::Forced(_)) => "a forced promise".to_string(),
+            Object::Promise(_) => "a promise".to_string(),
+            Object::Env(ref env) => format!("an environment with {} bindings", env.names().count()),
+            Object::Empty(_) => "an empty memory cell".to_string(),
+        }
+    }
+}
+
+/// Runs a comma command, returning the text to show the user.
+fn run_command(interpreter: &mut Interpreter, command: &str) -> Result<String, &'static str> {
+    let command = command.trim();
+    let (name, arg) = match command.find(char::is_whitespace) {
+        Some(i) => (&command[..i], command[i..].trim()),
+        None => (command, ""),
+    };
+    match name {
+        "time" => {
+            let forms = guarded(interpreter, |i| i.read(arg))?;
+            let allocations = interpreter.memory().allocations();
+            let start = Instant::now();
+            let res = guarded(interpreter, |i| i.eval_all(&forms))?;
+            let elapsed = start.elapsed();
+            let allocations = interpreter.memory().allocations() - allocations;
+            Ok(format!("{}\n; {:?} elapsed, {} allocations", interpreter.to_string(res), elapsed, allocations))
+        }
+        "expand" => {
+            let forms = guarded(interpreter, |i| i.read(arg))?;
+            let mut out = Vec::with_capacity(forms.len());
+            for form in forms {
+                let expanded = guarded(interpreter, |i| i.expand(form))?;
+                out.push(interpreter.to_string(expanded));
+            }
+            Ok(out.join("\n"))
+        }
+        "describe" => {
+            let res = guarded(interpreter, |i| i.eval_string(arg))?;
+            Ok(format!("{} is {}", arg, describe(interpreter, res)))
+        }
+        "load" => {
+            let source = fs::read_to_string(arg).or(Err("could not read file"))?;
+            let forms = guarded(interpreter, |i| i.read(&source))?;
+            guarded(interpreter, |i| i.eval_all(&forms))?;
+            Ok(format!("; loaded {}", arg))
+        }
+        "reset" => {
+            *interpreter = Interpreter::default();
+            Ok("; interpreter reset".to_string())
+        }
+        "help" => Ok(HELP.to_string()),
+        _ => Err("unknown command, try ,help"),
+    }
+}
+
+pub fn run() {
+    let mut interpreter = Interpreter::default();
+    let mut editor = Editor::new(history_file());
+    loop {
+        let source = match read_input(&mut editor, &interpreter) {
+            Ok(Some(source)) => source,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Error reading input: {}", e);
+                break;
+            }
+        };
+        if source.trim().is_empty() {
+            continue;
+        }
+        let entry: Vec<&str> = source.lines().map(|l| l.trim()).collect();
+        editor.add_history(&entry.join(" "));
+
+        let res = match source.trim().strip_prefix(',') {
+            Some(command) => run_command(&mut interpreter, command),
+            None => guarded(&mut interpreter, |i| i.eval_string(&source)).map(|res| interpreter.to_string(res)),
+        };
+        match res {
+            Ok(out) => println!("{}", out),
+            Err(e) => println!("Error: {}", e),
+        }
+        // nothing outside the interpreter holds values between inputs
+        interpreter.collect_garbage();
+    }
+    if let Err(e) = editor.save_history() {
+        eprintln!("Error saving history: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completions() {
+        let mut interpreter = Interpreter::new(500);
+        interpreter.eval_string("(define (cadr x) (car (cdr x)))").unwrap();
+        assert_eq!(completions(&interpreter, "ca"), vec!["cadr", "car"]);
+        assert_eq!(completions(&interpreter, "set"), vec!["set!", "set-car!", "set-cdr!"]);
+        assert_eq!(completions(&interpreter, "lam"), vec!["lambda"]);
+        assert!(completions(&interpreter, "zzz").is_empty());
+    }
+
+    #[test]
+    fn test_completed_forms_are_supported() {
+        // a keyword the evaluator does not handle would be looked up as a
+        // variable, or panic
+        let mut interpreter = Interpreter::new(500);
+        for form in SPECIAL_FORMS {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| interpreter.eval_string(&format!("({})", form))));
+            assert!(res.is_ok(), "{} panics", form);
+            assert_ne!(res.unwrap(), Err("Unbound variable"), "{} is not evaluated", form);
+        }
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        let mut interpreter = Interpreter::new(600);
+        let res = guarded(&mut interpreter, |i| i.eval_string("(define (f) (+ 1 (f))) (f)"));
+        assert_eq!(res, Err("Out of memory"));
+        assert_eq!(guarded(&mut interpreter, |i| i.eval_string("(+ 1 2)")), Ok(SExpr::Int(3)));
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut interpreter = Interpreter::new(500);
+        let timed = run_command(&mut interpreter, "time (cons 1 (cons 2 ()))").unwrap();
+        assert!(timed.starts_with("(1 2)\n; "));
+        assert!(timed.ends_with(" 2 allocations"));
+
+        assert_eq!(run_command(&mut interpreter, "expand (define (f x) (cond (x 1) (else 2)))").unwrap(),
+            "(define f (lambda (x) (if x (begin 1) (begin 2))))");
+
+        interpreter.eval_string("(define (f a b) a)").unwrap();
+        assert_eq!(run_command(&mut interpreter, "describe f").unwrap(),
+            "f is a compound procedure with parameters (a b)");
+        assert_eq!(run_command(&mut interpreter, "describe car").unwrap(),
+            "car is a primitive procedure (Car)");
+        assert_eq!(run_command(&mut interpreter, "describe (list 1 2)").unwrap(), "(list 1 2) is a list of length 2");
+        assert_eq!(run_command(&mut interpreter, "describe g"), Err("Unbound variable"));
+
+        let path = env::temp_dir().join(format!("rscheme-load-{}.scm", std::process::id()));
+        fs::write(&path, "(define x 1)\n(define (g) (+ x 1))\n").unwrap();
+        run_command(&mut interpreter, &format!("load {}", path.display())).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(interpreter.eval_string("(g)"), Ok(SExpr::Int(2)));
+        assert_eq!(run_command(&mut interpreter, "load /nonexistent/file.scm"), Err("could not read file"));
+
+        run_command(&mut interpreter, "reset").unwrap();
+        assert_eq!(interpreter.eval_string("x"), Err("Unbound variable"));
+        assert_eq!(run_command(&mut interpreter, "frobnicate"), Err("unknown command, try ,help"));
+    }
+
+    #[test]
+    fn test_open_parens() {
+        assert_eq!(open_parens("(+ 1 2)"), Some(0));
+        assert_eq!(open_parens("(define (f x)\n"), Some(1));
+        assert_eq!(open_parens("(define (f x)\n  (if (= x 0)\n"), Some(2));
+        assert_eq!(open_parens("(f \"(\""), Some(1));
+        assert_eq!(open_parens("1)"), None);
+        assert_eq!(open_parens("(f))"), None);
+        assert_eq!(open_parens("(f \"abc\n"), None);
+        assert_eq!(open_parens("(f \"a\nb\")\n"), Some(0));
+    }
+
+    #[test]
+    fn test_in_string() {
+        assert!(in_string("(f \"abc\n"));
+        assert!(in_string("\"a\\"));
+        assert!(!in_string("(f \"abc\")"));
+        assert!(!in_string("(f \"abc\") \"\\q\""));
+        assert!(!in_string("(f"));
+    }
+}
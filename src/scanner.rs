@@ -2,17 +2,49 @@
 use std::str::Chars;
 use std::iter::Peekable;
 
+use crate::numbers;
+
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum Token<'a> {
     OpenParen,
+    OpenVector,
+    OpenBytevector,
     ClosedParen,
     Int(i64),
+    /// Any other numeric literal, like `1/3`, `#x1F` or an integer too
+    /// large for an i64; the parser reads it with `numbers::parse`.
+    Number(&'a str),
     Float(f64),
     Bool(bool),
+    Char(char),
+    /// The text between the quotes, escapes still written out; `unescape`
+    /// decodes it.
     Str(&'a str),
     Symbol(&'a str),
 }
 
+/// The characters of a string literal from the text of its `Token::Str`,
+/// which the scanner has checked.
+pub(crate) fn unescape(text: &str) -> Vec<char> {
+    let mut res = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            res.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('x') => {
+                let hex: String = chars.by_ref().take_while(|&ch| ch != ';').collect();
+                res.extend(u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32));
+            }
+            Some(name) => res.extend(STRING_ESCAPES.iter().find(|&&(n, _)| n == name).map(|&(_, ch)| ch)),
+            None => (),
+        }
+    }
+    res
+}
+
 #[derive(Debug)]
 pub struct ParseError {
     pub message: &'static str,
@@ -24,10 +56,34 @@ fn is_whitespace(ch: char) -> bool {
 }
 
 fn is_numeric(ch: char) -> bool {
-    (ch >= '0') && (ch <= '9')
+    ch.is_ascii_digit()
 }
 
-const MISSING_QUOTE: &str = "Missing quote '\"'";
+/// Characters written by name after `#\`, as in `#\space`.
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("null", '\0'),
+    ("alarm", '\x07'),
+    ("backspace", '\x08'),
+    ("escape", '\x1b'),
+    ("delete", '\x7f'),
+];
+
+/// The characters written after a backslash in a string, as in `"\n"`.
+pub const STRING_ESCAPES: &[(char, char)] = &[
+    ('"', '"'),
+    ('\\', '\\'),
+    ('n', '\n'),
+    ('t', '\t'),
+    ('r', '\r'),
+    ('a', '\x07'),
+];
+
+/// The error for a string that is not closed before the end of the source.
+pub const MISSING_QUOTE: &str = "Missing quote '\"'";
 const UNEXPECTED_QUOTE: &str = "Unexpected quote '\"'";
 const OPEN_PAREN_IN_ATOM: &str = "Found illegal opening paren '(' in atom";
 
@@ -52,29 +108,51 @@ impl<'a> Scanner<'a> {
         }
     }
 
-    pub fn scan_tokens(mut self) -> Result<Vec<Token<'a>>, ParseError> {
+    pub fn scan_tokens(self) -> Result<Vec<Token<'a>>, ParseError> {
+        Ok(self.scan_tokens_with_offsets()?.into_iter().map(|(token, _)| token).collect())
+    }
+
+    /// Like `scan_tokens`, pairing each token with the byte offset it starts at.
+    pub fn scan_tokens_with_offsets(mut self) -> Result<Vec<(Token<'a>, usize)>, ParseError> {
+        let mut offsets = Vec::new();
         while !self.at_end() {
             self.start = self.current;
+            let count = self.tokens.len();
             self.token()?;
+            if self.tokens.len() > count {
+                offsets.push(self.start);
+            }
         }
-        Ok(self.tokens)
+        Ok(self.tokens.into_iter().zip(offsets).collect())
     }
 
     fn parse_err(&self, message: &'static str) -> Result<(), ParseError> {
-        Err(ParseError { message: message, line: self.line })
+        Err(ParseError { message, line: self.line })
     }
 
     fn token(&mut self) -> Result<(), ParseError> {
         match self.advance().unwrap() {
             ' ' => Ok(()),
             '\n' => { self.line += 1; Ok(()) }
+            ';' => {
+                while self.peek().is_some_and(|ch| ch != '\n') {
+                    self.advance();
+                }
+                Ok(())
+            }
             '(' => { self.tokens.push(Token::OpenParen); Ok(()) }
             ')' => { self.tokens.push(Token::ClosedParen); Ok(()) }
             '+' | '-' => if self.is_more_token() { self.int() } else { self.symbol() }
             ch if is_numeric(ch) => self.int(),
             '.' => if self.is_more_token() { self.float(false) } else { self.symbol() }
             '"' => self.string(),
-            '#' => self.bool_(),
+            '#' => match self.peek() {
+                Some('\\') => { self.advance(); self.char_() }
+                Some('(') => { self.advance(); self.tokens.push(Token::OpenVector); Ok(()) }
+                Some('u') => self.bytevector(),
+                Some(ch) if "xXbBoOdDeEiI".contains(ch) => self.prefixed_number(),
+                _ => self.bool_(),
+            }
             _ => self.symbol(),
         }
     }
@@ -83,6 +161,7 @@ impl<'a> Scanner<'a> {
         while self.is_more_token() {
             match self.advance().unwrap() {
                 ch if is_numeric(ch) => (),
+                '/' => return self.rational(),
                 '.' => return self.float(false),
                 'e' | 'E' => return match self.peek() {
                     None => self.symbol(),
@@ -97,6 +176,46 @@ impl<'a> Scanner<'a> {
         Ok(())
     }
 
+    fn rational(&mut self) -> Result<(), ParseError> {
+        // a sign alone is no numerator, so `-/2` is a symbol
+        let numerator = self.source.get(self.start..self.current - 1).unwrap();
+        if !numerator.ends_with(is_numeric) {
+            return self.symbol();
+        }
+        let mut digits = 0;
+        while self.is_more_token() {
+            match self.advance().unwrap() {
+                ch if is_numeric(ch) => digits += 1,
+                '(' => return self.parse_err(OPEN_PAREN_IN_ATOM),
+                _ => return self.symbol(),
+            }
+        }
+        if digits == 0 {
+            return self.symbol();
+        }
+        let slice = self.source.get(self.start..self.current).unwrap();
+        if numbers::parse(slice, 10).is_none() {
+            return self.parse_err("invalid number");
+        }
+        self.tokens.push(Token::Number(slice));
+        Ok(())
+    }
+
+    // A number starting with radix or exactness prefixes, like `#e#x10`.
+    fn prefixed_number(&mut self) -> Result<(), ParseError> {
+        while self.is_more_token() {
+            if self.advance().unwrap() == '(' {
+                return self.parse_err(OPEN_PAREN_IN_ATOM);
+            }
+        }
+        let slice = self.source.get(self.start..self.current).unwrap();
+        if numbers::parse(slice, 10).is_none() {
+            return self.parse_err("invalid number");
+        }
+        self.tokens.push(Token::Number(slice));
+        Ok(())
+    }
+
     fn float(&mut self, mut exponent_consumed: bool) -> Result<(), ParseError> {
         if !exponent_consumed {
             while self.is_more_token() {
@@ -143,18 +262,43 @@ impl<'a> Scanner<'a> {
                 None => return self.parse_err(MISSING_QUOTE),
                 Some(ch) => match ch {
                     '"' => { self.add_string_token(); return Ok(()); }
-                    '\n' => return self.parse_err(MISSING_QUOTE),
+                    '\n' => self.line += 1,
+                    '\\' => self.escape()?,
                     _ => ()
                 }
             }
         }
     }
 
+    // What follows a backslash in a string: one of `STRING_ESCAPES` or a
+    // code point like \x3bb;
+    fn escape(&mut self) -> Result<(), ParseError> {
+        match self.advance() {
+            None => self.parse_err(MISSING_QUOTE),
+            Some('x') => {
+                let start = self.current;
+                loop {
+                    match self.advance() {
+                        Some(';') => break,
+                        Some(ch) if ch.is_ascii_hexdigit() => (),
+                        _ => return self.parse_err("ill formed escape in string"),
+                    }
+                }
+                let hex = self.source.get(start..self.current-1).unwrap();
+                match u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+                    Some(_) => Ok(()),
+                    None => self.parse_err("ill formed escape in string"),
+                }
+            }
+            Some(ch) if STRING_ESCAPES.iter().any(|&(name, _)| name == ch) => Ok(()),
+            Some(_) => self.parse_err("unknown escape in string"),
+        }
+    }
+
     fn symbol(&mut self) -> Result<(), ParseError> {
         while self.is_more_token() {
-            match self.advance().unwrap() {
-                '(' => return self.parse_err(OPEN_PAREN_IN_ATOM),
-                _ => ()
+            if self.advance().unwrap() == '(' {
+                return self.parse_err(OPEN_PAREN_IN_ATOM);
             }
         }
 
@@ -177,10 +321,51 @@ impl<'a> Scanner<'a> {
         }
     }
 
+    // #\a, #\space or #\x3bb; the first character is taken even if it would
+    // end a token, so that #\( and #\  work.
+    fn char_(&mut self) -> Result<(), ParseError> {
+        if self.advance().is_none() {
+            return self.parse_err("expected character after '#\\'");
+        }
+        while self.is_more_token() {
+            if self.advance().unwrap() == '(' {
+                return self.parse_err(OPEN_PAREN_IN_ATOM);
+            }
+        }
+        let name = self.source.get(self.start+2..self.current).unwrap();
+        let mut chars = name.chars();
+        let first = chars.next().unwrap();
+        let ch = if chars.next().is_none() {
+            Some(first)
+        } else if let Some(&(_, ch)) = CHAR_NAMES.iter().find(|(n, _)| *n == name) {
+            Some(ch)
+        } else if let Some(hex) = name.strip_prefix('x') {
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        } else {
+            None
+        };
+        match ch {
+            Some(ch) => { self.tokens.push(Token::Char(ch)); Ok(()) }
+            None => self.parse_err("unknown character name"),
+        }
+    }
+
+    fn bytevector(&mut self) -> Result<(), ParseError> {
+        for &expected in &['u', '8', '('] {
+            if self.advance() != Some(expected) {
+                return self.parse_err("expected '#u8('");
+            }
+        }
+        self.tokens.push(Token::OpenBytevector);
+        Ok(())
+    }
+
     fn add_int_token(&mut self) {
         let token_str = self.source.get(self.start..self.current).unwrap();
-        let parsed = token_str.parse::<i64>().ok().unwrap();
-        self.tokens.push(Token::Int(parsed));
+        match token_str.parse::<i64>() {
+            Ok(parsed) => self.tokens.push(Token::Int(parsed)),
+            Err(_) => self.tokens.push(Token::Number(token_str)),
+        }
     }
 
     fn add_float_token(&mut self) {
@@ -196,7 +381,13 @@ impl<'a> Scanner<'a> {
 
     fn add_symbol_token(&mut self) {
         let slice = self.source.get(self.start..self.current).unwrap();
-        self.tokens.push(Token::Symbol(slice));
+        let token = match slice {
+            "+inf.0" => Token::Float(f64::INFINITY),
+            "-inf.0" => Token::Float(f64::NEG_INFINITY),
+            "+nan.0" | "-nan.0" => Token::Float(f64::NAN),
+            _ => Token::Symbol(slice),
+        };
+        self.tokens.push(token);
     }
 
     fn at_end(&self) -> bool { 
@@ -206,7 +397,7 @@ impl<'a> Scanner<'a> {
     fn is_more_token(&mut self) -> bool {
         match self.peek() {
             None => false,
-            Some(ch) if is_whitespace(ch) || (ch == ')') => false,
+            Some(ch) if is_whitespace(ch) || (ch == ')') || (ch == ';') => false,
             _ => true,
         }
     }
@@ -222,10 +413,7 @@ impl<'a> Scanner<'a> {
     }
 
     fn peek(&mut self) -> Option<char> {
-        match self.iter.peek() {
-            Some(&ch) => Some(ch),
-            None => None,
-        }      
+        self.iter.peek().copied()
     }
 }
 
@@ -234,19 +422,19 @@ impl<'a> Scanner<'a> {
 mod tests {
     use super::*;
 
-    fn s(x: &'static str) -> Token { Token::Symbol(x) }
-    fn st(x: &'static str) -> Token { Token::Str(x) }
+    fn s(x: &'static str) -> Token<'static> { Token::Symbol(x) }
+    fn st(x: &'static str) -> Token<'static> { Token::Str(x) }
     fn i(x: i64) -> Token<'static> { Token::Int(x) }
     fn f(x: f64) -> Token<'static> { Token::Float(x) }
     fn op() -> Token<'static> { Token::OpenParen }
     fn cp() -> Token<'static> { Token::ClosedParen }
     
-    fn tokens(x: &'static str) -> Result<Vec<Token>, ParseError> { 
+    fn tokens(x: &'static str) -> Result<Vec<Token<'static>>, ParseError> { 
         let scanner = Scanner::new(x);
         scanner.scan_tokens()
     }
 
-    fn scan_ok(x: &'static str, expected: Vec<Token>) {
+    fn scan_ok(x: &'static str, expected: Vec<Token<'static>>) {
         let res = tokens(x).expect("err");
         assert_eq!(res, expected);
     }
@@ -268,6 +456,7 @@ mod tests {
         let same = vec![
             "(+ abc def)", "( + abc def)", "(+ abc def )",
             "(+  abc    def)", "    (+ abc def)  ", "(+\nabc\ndef)",
+            "(+ abc ; (comment\n def)", "(+ abc;comment\ndef); (",
         ];
         for i in same { tests.push((i, vec![op(), s("+"), s("abc"), s("def"), cp()])); }
 
@@ -278,6 +467,9 @@ mod tests {
         ];
 
         for x in errs { scan_err(x); }
+
+        let offsets = Scanner::new("(a \"λ\" #(1))").scan_tokens_with_offsets().unwrap();
+        assert_eq!(offsets.iter().map(|&(_, at)| at).collect::<Vec<_>>(), vec![0, 1, 3, 8, 10, 11, 12]);
     }
 
     #[test]
@@ -291,6 +483,13 @@ mod tests {
             ("--5123", vec![s("--5123")]),
             ("-+5123", vec![s("-+5123")]),
             ("5-5", vec![s("5-5")]),
+            ("9223372036854775808", vec![Token::Number("9223372036854775808")]),
+            ("-9223372036854775808", vec![i(i64::MIN)]),
+            ("-1/3", vec![Token::Number("-1/3")]),
+            ("1/", vec![s("1/")]),
+            ("1/x", vec![s("1/x")]),
+            ("1/2/3", vec![s("1/2/3")]),
+            ("-/2", vec![s("-/2")]),
         ];
 
         let same = vec![
@@ -310,15 +509,20 @@ mod tests {
             (r#" ("abc") "#, vec![op(), st("abc"), cp()]),
             (r#" "(abc))())(" "#, vec![st("(abc))())(")]),
             (r#"  ("abc" "def" ("ijk")) "#, vec![op(), st("abc"), st("def"), op(), st("ijk"), cp(), cp()]),
+            (r#" "a\"b\\" "#, vec![st(r#"a\"b\\"#)]),
+            (r#" "\n\t\x3bb;" "#, vec![st(r#"\n\t\x3bb;"#)]),
+            (" \"a\nb\"  ", vec![st("a\nb")]),
         ];
 
         for (x, y) in tests { scan_ok(x, y); }
 
         let errs = vec![
-            " \"a\nb\"  ", "(\")"
+            "(\")", r#""a\""#, r#""\q""#, r#""\x3bb""#, r#""\xd800;""#,
         ];
 
         for x in errs { scan_err(x); }
+
+        assert_eq!(unescape(r#"a\"b\\c\n\x3bb;"#).into_iter().collect::<String>(), "a\"b\\c\nλ");
     }
 
     #[test]
@@ -363,6 +567,42 @@ mod tests {
         scan_err("#b");
     }
 
+    #[test]
+    fn test_number_syntax() {
+        scan_ok("#x1F", vec![Token::Number("#x1F")]);
+        scan_ok("(#e1.5 #i#b101)", vec![op(), Token::Number("#e1.5"), Token::Number("#i#b101"), cp()]);
+        scan_ok("+inf.0 -inf.0", vec![f(f64::INFINITY), f(f64::NEG_INFINITY)]);
+        assert!(matches!(tokens("+nan.0").unwrap()[..], [Token::Float(x)] if x.is_nan()));
+        scan_ok("inf.0", vec![s("inf.0")]);
+        scan_err("#x1G");
+        scan_err("#x#x1");
+        scan_err("#e+inf.0");
+        scan_err("1/0");
+    }
+
+    #[test]
+    fn test_char() {
+        let c = Token::Char;
+        scan_ok("#\\a", vec![c('a')]);
+        scan_ok("(#\\λ #\\space)", vec![op(), c('λ'), c(' '), cp()]);
+        scan_ok("#\\newline #\\x3bb #\\x", vec![c('\n'), c('λ'), c('x')]);
+        scan_ok("(#\\( #\\))", vec![op(), c('('), c(')'), cp()]);
+        scan_ok("#\\ ", vec![c(' ')]);
+        scan_err("#\\");
+        scan_err("#\\spac");
+        scan_err("#\\xd800");
+    }
+
+    #[test]
+    fn test_vector() {
+        scan_ok("#(1 #t)", vec![Token::OpenVector, i(1), Token::Bool(true), cp()]);
+        scan_ok("#()", vec![Token::OpenVector, cp()]);
+        scan_ok("#u8(1 255)", vec![Token::OpenBytevector, i(1), i(255), cp()]);
+        scan_err("#u8");
+        scan_err("#u16(1)");
+        scan_ok("(#\\# #(#\\())", vec![op(), Token::Char('#'), Token::OpenVector, Token::Char('('), cp(), cp()]);
+    }
+
     #[test]
     fn test_advance() {
         let mut scanner = Scanner::new("123");
@@ -3,19 +3,35 @@ use std::collections::HashMap;
 // use crate::parser::Parser;
 // use crate::scanner::{Scanner, ParseError};
 use crate::interpreter::Primitive;
+use crate::native::Native;
+use crate::host::HostObject;
+use crate::bigint::BigInt;
+use crate::numbers::{self, Rational};
+use crate::hashtables::HashTable;
+use crate::records::{RecordType, Record, RecordProcedure};
+use crate::promises::Promise;
+use crate::symbol::{Symbol, SymbolTable};
+use crate::scanner::CHAR_NAMES;
+
+pub const OUT_OF_MEMORY: &str = "Out of memory";
 
 #[derive(PartialEq, Copy, Clone, Debug)]
-pub enum SExpr<'s> {
+pub enum SExpr {
     Nil,
     Int(i64),
     Float(f64),
     Bool(bool),
-    Str(&'s str),
-    Sym(&'s str),
+    Char(char),
+    Sym(Symbol),
+    /// The end-of-file object.
+    Eof,
+    /// The value of an expression R7RS leaves unspecified, such as a
+    /// one-armed `if` whose test is false.
+    Unspecified,
     Ref(usize),
 }
 
-impl<'s> SExpr<'s> {
+impl SExpr {
     pub fn as_bool(&self) -> bool {
         match *self {
             SExpr::Bool(b) => b,
@@ -24,43 +40,68 @@ impl<'s> SExpr<'s> {
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub enum Object<'s> {
-    Pair(SExpr<'s>, SExpr<'s>),
+#[derive(PartialEq, Debug)]
+pub enum Object {
+    Pair(SExpr, SExpr),
+    BigInt(BigInt),
+    Rational(Rational),
     PrimitiveProcedure(Primitive),
-    CompoundProcedure(SExpr<'s>),
-    Env(Environment<'s>),
+    CompoundProcedure(SExpr),
+    NativeProcedure(Native),
+    Env(Environment),
+    /// Code points rather than UTF-8, so indexing is constant time.
+    Str(Vec<char>),
+    Vector(Vec<SExpr>),
+    Bytevector(Vec<u8>),
+    HashTable(HashTable),
+    RecordType(RecordType),
+    Record(Record),
+    RecordProcedure(RecordProcedure),
+    Host(HostObject),
+    Promise(Promise),
     Empty(Option<usize>),
 }
 
 
 #[derive(PartialEq, Debug, Clone)]
-pub struct Environment<'s> {
-    env: HashMap<&'s str, SExpr<'s>>,
-    enclosing: SExpr<'s>,
+pub struct Environment {
+    env: HashMap<Symbol, SExpr>,
+    enclosing: SExpr,
 }
 
-impl<'s> Environment<'s> {
-    pub fn new(enclosing: SExpr<'s>) -> Self {
+impl Environment {
+    pub fn new(enclosing: SExpr) -> Self {
         Environment {
             env: HashMap::new(),
             enclosing,
         }
     }
 
-    pub fn get<'m>(&self, k: &'s str, mem: &'m Memory<'s>) -> Result<SExpr<'s>, ()> {
-        match self.env.get(k) {
+    pub fn get(&self, k: Symbol, mem: &Memory) -> Result<SExpr, &'static str> {
+        match self.env.get(&k) {
             Some(&e) => Ok(e),
             None => mem.env_get(k, self.enclosing),
         }
     }
 
-    pub fn insert(&mut self, k: &'s str, e: SExpr<'s>) {
+    pub fn insert(&mut self, k: Symbol, e: SExpr) {
         self.env.insert(k, e);
     }
 
-    pub fn set<'m>(&mut self, k: &'s str, e: SExpr<'s>, mem: &'m mut Memory<'s>) -> Result<(), ()> {
-        if self.env.contains_key(k) {
+    pub fn names(&self) -> impl Iterator<Item = Symbol> + '_ {
+        self.env.keys().copied()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = SExpr> + '_ {
+        self.env.values().copied()
+    }
+
+    pub fn enclosing(&self) -> SExpr {
+        self.enclosing
+    }
+
+    pub fn set(&mut self, k: Symbol, e: SExpr, mem: &mut Memory) -> Result<(), &'static str> {
+        if self.env.contains_key(&k) {
             self.insert(k, e);
             Ok(())
         } else {
@@ -70,78 +111,156 @@ impl<'s> Environment<'s> {
 }
 
 #[derive(PartialEq, Debug)]
-pub struct Memory<'s>  {
-    mem: Vec<Object<'s>>,
+pub struct Memory  {
+    mem: Vec<Object>,
     first: usize,
+    available: usize,
+    allocations: usize,
+    relocations: u64,
+    // the epoch each cell was allocated in, see `next_epoch`
+    born: Vec<u64>,
+    epoch: u64,
+    symbols: SymbolTable,
 }
 
-impl<'s> Memory<'s>  {
+impl Memory  {
     pub fn new(size: usize) -> Self {
         let mut mem = Vec::with_capacity(size);
         for i in 0..size-1 {
             mem.push(Object::Empty(Some(i+1)));
         }
         mem.push(Object::Empty(None));
-        Memory { mem, first: 0 }
+        // the last cell ends the free list and is never handed out
+        Memory {
+            mem, first: 0, available: size - 1, allocations: 0, relocations: 0,
+            born: vec![0; size], epoch: 0, symbols: SymbolTable::new(),
+        }
     }
 
-    pub fn alloc(&mut self, obj: Object<'s>) -> SExpr<'s> {
+    // Unwinds with `OUT_OF_MEMORY` as the payload when no object is free,
+    // which the interpreter turns back into an error.
+    pub fn alloc(&mut self, obj: Object) -> SExpr {
         match self.mem[self.first] {
             Object::Empty(Some(next)) => {
                 self.mem[self.first] = obj;
+                self.born[self.first] = self.epoch;
                 let r = self.first;
                 self.first = next;
+                self.available -= 1;
+                self.allocations += 1;
                 SExpr::Ref(r)
             }
-            Object::Empty(None) => panic!("Out of memory"),
+            Object::Empty(None) => std::panic::resume_unwind(Box::new(OUT_OF_MEMORY)),
             _ => panic!("Head of free list is not an empty object"),
         }
     }
 
-    pub fn cons(&mut self, left: SExpr<'s>, right: SExpr<'s>) -> SExpr<'s> {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        self.symbols.intern(name)
+    }
+
+    pub fn find_symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name)
+    }
+
+    pub fn symbol_name(&self, sym: Symbol) -> &str {
+        self.symbols.name(sym)
+    }
+
+    /// Returns the cell at `addr` to the free list, dropping its object.
+    pub(crate) fn free(&mut self, addr: usize) {
+        if let Object::Empty(_) = self.mem[addr] {
+            return;
+        }
+        let obj = ::std::mem::replace(&mut self.mem[addr], Object::Empty(Some(self.first)));
+        self.first = addr;
+        self.available += 1;
+        drop(obj);
+    }
+
+    /// Number of objects this memory can hold.
+    pub fn capacity(&self) -> usize {
+        self.mem.len()
+    }
+
+    /// Number of free cells.
+    pub fn available(&self) -> usize {
+        self.available
+    }
+
+    /// Number of objects allocated since this memory was created.
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+
+    /// Number of times objects have been moved to new addresses. Anything
+    /// derived from addresses, like the buckets of eq? hash tables, must be
+    /// recomputed when this changes.
+    pub fn relocations(&self) -> u64 {
+        self.relocations
+    }
+
+    /// Records that objects have moved; called by anything that relocates them.
+    pub fn relocated(&mut self) {
+        self.relocations += 1;
+    }
+
+    /// Starts a new epoch, returning it. Objects allocated from now on are
+    /// born in it.
+    pub(crate) fn next_epoch(&mut self) -> u64 {
+        self.epoch += 1;
+        self.epoch
+    }
+
+    /// The epoch the object at `addr` was allocated in.
+    pub(crate) fn born(&self, addr: usize) -> u64 {
+        self.born[addr]
+    }
+
+    pub fn cons(&mut self, left: SExpr, right: SExpr) -> SExpr {
         self.alloc(Object::Pair(left, right))
     }
 
-    pub fn car(&self, pair: SExpr<'s>) -> Result<SExpr<'s>, ()> {
+    pub fn car(&self, pair: SExpr) -> Result<SExpr, &'static str> {
         if let SExpr::Ref(addr) = pair {
             if let Some(&Object::Pair(car, _)) = self.mem.get(addr) {
                 return Ok(car);
             }
         }
-        Err(())
+        Err("type error: expected pair")
     }
 
-    pub fn cdr(&self, pair: SExpr<'s>) -> Result<SExpr<'s>, ()> {
+    pub fn cdr(&self, pair: SExpr) -> Result<SExpr, &'static str> {
         if let SExpr::Ref(addr) = pair {
             if let Some(&Object::Pair(_, cdr)) = self.mem.get(addr) {
                 return Ok(cdr);
             }
         }
-        Err(())
+        Err("type error: expected pair")
     }
 
-    pub fn set_car(&mut self, pair: SExpr<'s>, value: SExpr<'s>) -> Result<(), ()> {
+    pub fn set_car(&mut self, pair: SExpr, value: SExpr) -> Result<(), &'static str> {
         if let SExpr::Ref(addr) = pair {
             if let Some(Object::Pair(ref mut car, _)) = self.mem.get_mut(addr) {
                 *car = value;
                 return Ok(());
             }
         }
-        Err(()) // type error
+        Err("type error: expected pair")
     }
 
-    pub fn set_cdr(&mut self, pair: SExpr<'s>, value: SExpr<'s>) -> Result<(), ()> {
+    pub fn set_cdr(&mut self, pair: SExpr, value: SExpr) -> Result<(), &'static str> {
         if let SExpr::Ref(addr) = pair {
             if let Some(Object::Pair(_, ref mut cdr)) = self.mem.get_mut(addr) {
                 *cdr = value;
                 return Ok(());
             }
         }
-        Err(()) // type error
+        Err("type error: expected pair")
     }
 
-    pub fn list_from_vec(&mut self, vec: Vec<SExpr<'s>>) -> SExpr<'s> {
-        let head = match vec.get(0) {
+    pub fn list_from_vec(&mut self, vec: Vec<SExpr>) -> SExpr {
+        let head = match vec.first() {
             Some(&e) => self.cons(e, SExpr::Nil),
             None => return SExpr::Nil, // input vec has length 0
         };
@@ -154,45 +273,64 @@ impl<'s> Memory<'s>  {
         head
     }
 
-    pub fn vec_from_list(&self, list: SExpr<'s>) -> Result<Vec<SExpr<'s>>, ()> {
+    pub fn vec_from_list(&self, list: SExpr) -> Result<Vec<SExpr>, &'static str> {
         let mut vec = Vec::new();
         let mut curr = list;
         loop {
             match curr {
                 SExpr::Ref(addr) => match &self.mem[addr] {
                     &Object::Pair(left, right) => { vec.push(left); curr = right; }
-                    _ => return Err(()), // type error
+                    _ => return Err("type error: expected list"),
                 },
                 SExpr::Nil => break,
-                _ => return Err(()), // type error/ill formed list
+                _ => return Err("type error: expected list"),
             }
         }
         Ok(vec)
     }
 
-    pub fn env_get(&self,  k: &'s str, e: SExpr<'s>) -> Result<SExpr<'s>, ()> {
+    pub fn env_get(&self,  k: Symbol, e: SExpr) -> Result<SExpr, &'static str> {
         match e {
             SExpr::Ref(addr) => match &self.mem[addr] {
-                &Object::Env(ref env) => env.get(k, self),
-                _ => Err(()), // type error
+                Object::Env(env) => env.get(k, self),
+                _ => Err("type error: expected environment"),
             }
-            SExpr::Nil => Err(()), // not found
-            _ => Err(()), // type error
+            SExpr::Nil => Err("Unbound variable"),
+            _ => Err("type error: expected environment"),
         }
     }
 
-    pub fn env_insert(&mut self, env: SExpr<'s>, k: &'s str, e: SExpr<'s>) -> Result<(), ()> {
+    /// Names bound in `e` and every frame enclosing it, innermost first.
+    pub fn env_names(&self, e: SExpr) -> Result<Vec<Symbol>, &'static str> {
+        let mut names = Vec::new();
+        let mut curr = e;
+        loop {
+            match curr {
+                SExpr::Ref(addr) => match &self.mem[addr] {
+                    Object::Env(env) => {
+                        names.extend(env.names());
+                        curr = env.enclosing();
+                    }
+                    _ => return Err("type error: expected environment"),
+                }
+                SExpr::Nil => return Ok(names),
+                _ => return Err("type error: expected environment"),
+            }
+        }
+    }
+
+    pub fn env_insert(&mut self, env: SExpr, k: Symbol, e: SExpr) -> Result<(), &'static str> {
         match env {
             SExpr::Ref(addr) => match &mut self.mem[addr] {
                 &mut Object::Env(ref mut env) => { env.insert(k, e); Ok(()) },
-                _ => Err(()), // type error
+                _ => Err("type error: expected environment"),
             }
-            SExpr::Nil => Err(()), // not found
-            _ => Err(()), // type error
+            SExpr::Nil => Err("type error: expected environment"),
+            _ => Err("type error: expected environment"),
         }
     }
 
-    pub fn env_set(&mut self, env: SExpr<'s>, k: &'s str, v: SExpr<'s>) -> Result<(), ()> {
+    pub fn env_set(&mut self, env: SExpr, k: Symbol, v: SExpr) -> Result<(), &'static str> {
         match env {
             SExpr::Ref(addr) => match ::std::mem::replace(&mut self.mem[addr], Object::Empty(None)) { 
                 Object::Env(mut env) => { 
@@ -200,100 +338,288 @@ impl<'s> Memory<'s>  {
                     self.mem[addr] = Object::Env(env);
                     res
                 },
-                _ => Err(()),
+                _ => Err("type error: expected environment"),
             }
-            SExpr::Nil => Err(()), // not found
-            _ => Err(()) // type error
+            SExpr::Nil => Err("Unbound variable"),
+            _ => Err("type error: expected environment")
         }
     }
 
-    pub fn get(&self, addr: usize) -> &Object<'s> {
+    pub fn get(&self, addr: usize) -> &Object {
         &self.mem[addr]
     }
 
-    pub fn get_nth_ref(&self, e: SExpr<'s>, n: usize) -> Result<SExpr<'s>, ()> {
+    pub fn get_mut(&mut self, addr: usize) -> &mut Object {
+        &mut self.mem[addr]
+    }
+
+    pub fn str_ref(&self, e: SExpr) -> Result<&[char], &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::Str(ref s) => Ok(s),
+                _ => Err("type error: expected string"),
+            }
+            _ => Err("type error: expected string"),
+        }
+    }
+
+    pub fn str_mut(&mut self, e: SExpr) -> Result<&mut Vec<char>, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::Str(ref mut s) => Ok(s),
+                _ => Err("type error: expected string"),
+            }
+            _ => Err("type error: expected string"),
+        }
+    }
+
+    pub fn vector_ref(&self, e: SExpr) -> Result<&Vec<SExpr>, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::Vector(ref v) => Ok(v),
+                _ => Err("type error: expected vector"),
+            }
+            _ => Err("type error: expected vector"),
+        }
+    }
+
+    pub fn vector_mut(&mut self, e: SExpr) -> Result<&mut Vec<SExpr>, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::Vector(ref mut v) => Ok(v),
+                _ => Err("type error: expected vector"),
+            }
+            _ => Err("type error: expected vector"),
+        }
+    }
+
+    pub fn bytevector_ref(&self, e: SExpr) -> Result<&Vec<u8>, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::Bytevector(ref bytes) => Ok(bytes),
+                _ => Err("type error: expected bytevector"),
+            }
+            _ => Err("type error: expected bytevector"),
+        }
+    }
+
+    pub fn bytevector_mut(&mut self, e: SExpr) -> Result<&mut Vec<u8>, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::Bytevector(ref mut bytes) => Ok(bytes),
+                _ => Err("type error: expected bytevector"),
+            }
+            _ => Err("type error: expected bytevector"),
+        }
+    }
+
+    pub fn hash_table_ref(&self, e: SExpr) -> Result<&HashTable, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::HashTable(ref table) => Ok(table),
+                _ => Err("type error: expected hash table"),
+            }
+            _ => Err("type error: expected hash table"),
+        }
+    }
+
+    pub fn hash_table_mut(&mut self, e: SExpr) -> Result<&mut HashTable, &'static str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::HashTable(ref mut table) => Ok(table),
+                _ => Err("type error: expected hash table"),
+            }
+            _ => Err("type error: expected hash table"),
+        }
+    }
+
+    /// The name a native procedure was registered under.
+    pub fn native_name(&self, e: SExpr) -> Option<&str> {
+        match e {
+            SExpr::Ref(addr) => match self.mem[addr] {
+                Object::NativeProcedure(ref native) => Some(&native.name),
+                _ => None,
+            }
+            _ => None,
+        }
+    }
+
+    /// The object `e` refers to, if it is a reference.
+    pub fn deref(&self, e: SExpr) -> Option<&Object> {
+        match e {
+            SExpr::Ref(addr) => Some(&self.mem[addr]),
+            _ => None,
+        }
+    }
+
+    pub fn get_nth_ref(&self, e: SExpr, n: usize) -> Result<SExpr, &'static str> {
         let mut curr = e;
         for _ in 0..n {
             if let SExpr::Ref(addr) = curr {
                 if let &Object::Pair(_, right) = &self.mem[addr] {
                     curr = right;
-                } else { return Err(()); }
-            } else { return Err(()); }
+                } else { return Err("type error: expected pair"); }
+            } else { return Err("type error: expected pair"); }
         }
         Ok(curr)
     }
 
-    pub fn to_string(&self, e: SExpr<'s>) -> String {
+    pub fn to_string(&self, e: SExpr) -> String {
         fn s(mem: &Memory, e: SExpr) -> String {
             match e {
                 SExpr::Int(i) => i.to_string(),
-                SExpr::Float(f) => f.to_string(),
+                SExpr::Float(f) => numbers::float_to_string(f),
                 SExpr::Bool(b) => b.to_string(),
+                SExpr::Char(c) => match CHAR_NAMES.iter().find(|&&(_, ch)| ch == c) {
+                    Some((name, _)) => format!("#\\{}", name),
+                    None if c.is_control() => format!("#\\x{:x}", c as u32),
+                    None => format!("#\\{}", c),
+                }
+                SExpr::Sym(sym) => mem.symbol_name(sym).to_string(),
                 SExpr::Nil => "()".to_string(),
+                SExpr::Eof => "#<eof>".to_string(),
+                SExpr::Unspecified => "#<unspecified>".to_string(),
                 SExpr::Ref(r) => match mem.get(r) {
-                    v @ Object::Pair(_, _) => {
+                    Object::Pair(_, _) => {
                         let mut acc = "(".to_string();
-                        let mut curr = v;
-                        loop {
-                            match curr {
-                                &Object::Pair(left, right) => {
-                                    acc.push_str(format!("{}", mem.to_string(left)).as_str());
-                                    match right {
-                                        SExpr::Ref(addr) => {
-                                            acc.push(' ');
-                                            curr = mem.get(addr);
-                                        }
-                                        SExpr::Nil => {
-                                            acc = format!("{})", acc);
-                                            break;
-                                        }
-                                        _ => {
-                                            acc = format!("{} . {})", acc, mem.to_string(right));
-                                            break;
-                                        }
+                        let mut curr = e;
+                        while let SExpr::Ref(addr) = curr {
+                            match *mem.get(addr) {
+                                Object::Pair(left, right) => {
+                                    if curr != e {
+                                        acc.push(' ');
                                     }
+                                    acc.push_str(s(mem, left).as_str());
+                                    curr = right;
                                 }
-                                _ => unimplemented!(),
+                                _ => break,
                             }
                         }
+                        match curr {
+                            SExpr::Nil => acc.push(')'),
+                            _ => acc = format!("{} . {})", acc, s(mem, curr)),
+                        }
                         acc
                     }
-                    _ => unimplemented!(),
+                    Object::BigInt(ref b) => b.to_string(),
+                    Object::Rational(ref r) => r.to_string(),
+                    Object::PrimitiveProcedure(_) => "#<primitive>".to_string(),
+                    Object::CompoundProcedure(_) => "#<procedure>".to_string(),
+                    Object::NativeProcedure(ref native) => format!("#<native {}>", native.name),
+                    Object::Env(_) => "#<environment>".to_string(),
+                    Object::Str(ref s) => write_string(s),
+                    Object::Vector(ref v) => {
+                        let elements: Vec<String> = v.iter().map(|&e| s(mem, e)).collect();
+                        format!("#({})", elements.join(" "))
+                    }
+                    Object::Bytevector(ref bytes) => {
+                        let elements: Vec<String> = bytes.iter().map(u8::to_string).collect();
+                        format!("#u8({})", elements.join(" "))
+                    }
+                    Object::HashTable(_) => "#<hash-table>".to_string(),
+                    Object::RecordType(ref t) => format!("#<record-type {}>", t.display_name()),
+                    Object::Record(ref r) => match mem.deref(r.rtype) {
+                        Some(Object::RecordType(t)) => {
+                            let mut acc = format!("#<record {}", t.display_name());
+                            for (&field, &value) in t.fields.iter().zip(&r.fields) {
+                                acc = format!("{} {}: {}", acc, mem.symbol_name(field), s(mem, value));
+                            }
+                            acc + ">"
+                        }
+                        _ => "#<record>".to_string(),
+                    }
+                    Object::RecordProcedure(_) => "#<procedure>".to_string(),
+                    Object::Host(ref host) => host.to_string(),
+                    Object::Promise(_) => "#<promise>".to_string(),
+                    Object::Empty(_) => "#<empty>".to_string(),
                 }
-                _ => "".to_string(),
             }
         }
         s(self, e)
     }
 }
 
+// `s` as a string literal that reads back as the same string.
+fn write_string(s: &[char]) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for &ch in s {
+        match ch {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            ch if ch.is_control() => res.push_str(&format!("\\x{:x};", ch as u32)),
+            ch => res.push(ch),
+        }
+    }
+    res.push('"');
+    res
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
-    fn i<'s>(i: i64) -> SExpr<'s> { SExpr::Int(i) }
-    fn sy<'s>(s: &'s str) -> SExpr<'s> { SExpr::Sym(s) }
+    fn i(i: i64) -> SExpr { SExpr::Int(i) }
 
     #[test]
     fn test_env() {
         let mut mem = Memory::new(100);
 
+        let test = mem.intern("test");
         let mut env1 = Environment::new(SExpr::Nil);
-        env1.insert("test", i(1));
+        env1.insert(test, i(1));
         let env1r = mem.alloc(Object::Env(env1));
 
-        assert_eq!(mem.env_get("test", env1r).unwrap(), i(1));
+        assert_eq!(mem.env_get(test, env1r).unwrap(), i(1));
 
         let env2 = Environment::new(env1r);
         let env2r = mem.alloc(Object::Env(env2));
 
-        assert_eq!(mem.env_get("test", env2r).unwrap(), i(1));
+        assert_eq!(mem.env_get(test, env2r).unwrap(), i(1));
 
         let mut env3 = Environment::new(env1r);
-        env3.insert("test", i(2));
+        env3.insert(test, i(2));
         let env3r = mem.alloc(Object::Env(env3));
 
-        assert_eq!(mem.env_get("test", env3r).unwrap(), i(2));   
+        assert_eq!(mem.env_get(test, env3r).unwrap(), i(2));   
+    }
+
+    #[test]
+    fn test_env_names() {
+        let mut mem = Memory::new(100);
+
+        let (a, b, c) = (mem.intern("a"), mem.intern("b"), mem.intern("c"));
+        let mut env1 = Environment::new(SExpr::Nil);
+        env1.insert(a, i(1));
+        env1.insert(b, i(2));
+        let env1r = mem.alloc(Object::Env(env1));
+
+        let mut env2 = Environment::new(env1r);
+        env2.insert(c, i(3));
+        let env2r = mem.alloc(Object::Env(env2));
+
+        let mut names = mem.env_names(env2r).unwrap();
+        assert_eq!(names[0], c);
+        names.sort();
+        assert_eq!(names, vec![a, b, c]);
+        assert!(mem.env_names(i(1)).is_err());
+    }
+
+    #[test]
+    fn test_free() {
+        let mut mem = Memory::new(3);
+        let a = mem.cons(i(1), i(2));
+        mem.cons(i(3), i(4));
+        if let SExpr::Ref(addr) = a {
+            mem.free(addr);
+            mem.free(addr);
+            assert_eq!(mem.cons(i(5), i(6)), SExpr::Ref(addr));
+        }
+        assert_eq!(mem.allocations(), 3);
+        assert_eq!(mem.available(), 0);
     }
 
     #[test]
@@ -303,5 +629,14 @@ mod tests {
         assert_eq!(mem.to_string(list), "(1 2 3)");
         let pair = mem.cons(i(1), i(2));
         assert_eq!(mem.to_string(pair), "(1 . 2)");
+        let a = SExpr::Sym(mem.intern("a"));
+        let b = mem.alloc(Object::Str(vec!['b']));
+        let list = mem.list_from_vec(vec![a, b, SExpr::Bool(true)]);
+        assert_eq!(mem.to_string(list), "(a \"b\" true)");
+        let procd = mem.alloc(Object::PrimitiveProcedure(Primitive::Add));
+        let pair = mem.cons(i(1), procd);
+        assert_eq!(mem.to_string(pair), "(1 . #<primitive>)");
+        let escaped = mem.alloc(Object::Str("\"a\\b\nc\u{7}".chars().collect()));
+        assert_eq!(mem.to_string(escaped), r#""\"a\\b\nc\x7;""#);
     }
 }
\ No newline at end of file
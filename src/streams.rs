@@ -0,0 +1,147 @@
+use crate::interpreter::Interpreter;
+use crate::predicates::is_list;
+use crate::promises::{self, Promise};
+use crate::sexpr::{SExpr, Object};
+
+// SRFI-41 streams. A stream is a promise of either the empty list or a pair
+// of a promise of the first element and the rest of the stream. The
+// `stream-cons` and `define-stream` forms are built into the interpreter.
+// The lazy procedures are written in Scheme with `define-stream`, whose
+// `delay-force` keeps long traversals in constant space. The ones that
+// walk a whole stream are Scheme loops too, so the part already walked can
+// be collected; the rest are native.
+
+const SCHEME_PROCEDURES: &str = "
+(define-stream (stream-map f s)
+  (if (stream-null? s) stream-null (stream-cons (f (stream-car s)) (stream-map f (stream-cdr s)))))
+(define-stream (stream-filter keep? s)
+  (cond ((stream-null? s) stream-null)
+        ((keep? (stream-car s)) (stream-cons (stream-car s) (stream-filter keep? (stream-cdr s))))
+        (else (stream-filter keep? (stream-cdr s)))))
+(define-stream (stream-take n s)
+  (if (if (> n 0) (stream-pair? s) #f)
+      (stream-cons (stream-car s) (stream-take (- n 1) (stream-cdr s)))
+      stream-null))
+(define stream->list
+  (let ((reverse reverse))
+    (define (loop elements s)
+      (if (stream-null? s) (reverse elements) (loop (cons (stream-car s) elements) (stream-cdr s))))
+    (lambda (s) (loop (quote ()) s))))
+(define (stream-fold f acc s)
+  (if (stream-null? s) acc (stream-fold f (f acc (stream-car s)) (stream-cdr s))))
+";
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    let null = interpreter.memory_mut().alloc(Object::Promise(Promise::Forced(SExpr::Nil)));
+    interpreter.define("stream-null", null).unwrap();
+    interpreter.define_builtin("stream-null?", 1, |interp, args| Ok(promises::force(interp, args[0])? == SExpr::Nil));
+    interpreter.define_builtin("stream-pair?", 1, |interp, args| {
+        if !matches!(interp.memory().deref(args[0]), Some(Object::Promise(_))) {
+            return Ok(false);
+        }
+        let pair = promises::force(interp, args[0])?;
+        Ok(interp.memory().car(pair).is_ok())
+    });
+    interpreter.define_builtin("stream-car", 1, |interp, args| {
+        let (first, _) = split(interp, args[0])?.ok_or("type error: expected stream pair")?;
+        Ok(first)
+    });
+    interpreter.define_builtin("stream-cdr", 1, |interp, args| {
+        let pair = promises::force(interp, args[0])?;
+        interp.memory().cdr(pair).or(Err("type error: expected stream pair"))
+    });
+    interpreter.define_builtin("list->stream", 1, |interp, args| {
+        if !is_list(interp.memory(), args[0]) {
+            return Err("type error: expected list");
+        }
+        let elements = interp.memory().vec_from_list(args[0]).unwrap();
+        let mem = interp.memory_mut();
+        let mut stream = mem.alloc(Object::Promise(Promise::Forced(SExpr::Nil)));
+        for e in elements.into_iter().rev() {
+            let first = mem.alloc(Object::Promise(Promise::Forced(e)));
+            let pair = mem.cons(first, stream);
+            stream = mem.alloc(Object::Promise(Promise::Forced(pair)));
+        }
+        Ok(stream)
+    });
+    interpreter.eval_string(SCHEME_PROCEDURES).unwrap();
+}
+
+// The first element and the rest of the stream `s`, or `None` at its end.
+fn split(interp: &mut Interpreter, s: SExpr) -> Result<Option<(SExpr, SExpr)>, &'static str> {
+    let slot = interp.root(s);
+    let res = split_rooted(interp, s);
+    interp.unroot(slot);
+    res
+}
+
+fn split_rooted(interp: &mut Interpreter, s: SExpr) -> Result<Option<(SExpr, SExpr)>, &'static str> {
+    let pair = promises::force(interp, s)?;
+    if pair == SExpr::Nil {
+        return Ok(None);
+    }
+    let mem = interp.memory();
+    let (first, rest) = match (mem.car(pair), mem.cdr(pair)) {
+        (Ok(first), Ok(rest)) => (first, rest),
+        _ => return Err("type error: expected stream"),
+    };
+    Ok(Some((promises::force(interp, first)?, rest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::eval_to_string as eval;
+
+    const INTEGERS: &str = "(define-stream (integers-from n) (stream-cons n (integers-from (+ n 1))))";
+
+    #[test]
+    fn test_basics() {
+        assert_eq!(eval("(stream->list (list->stream (list 1 2 3)))"), "(1 2 3)");
+        assert_eq!(eval("(stream-car (stream-cdr (stream-cons 1 (stream-cons 2 stream-null))))"), "2");
+        assert_eq!(eval("(list (stream-null? stream-null) (stream-pair? stream-null) (stream-pair? (list->stream (list 1))))"),
+                   "(true false true)");
+        assert_eq!(eval("(stream-pair? (list 1))"), "false");
+        assert_eq!(eval("(stream-car stream-null)"), "type error: expected stream pair");
+        assert_eq!(eval("(stream->list 1)"), "type error: expected stream");
+        assert_eq!(eval("(stream-cons 1)"), "ill formed stream-cons");
+    }
+
+    #[test]
+    fn test_laziness() {
+        assert_eq!(eval("(define s (stream-cons (car 1) (car 2))) (stream-pair? s)"), "true");
+        assert_eq!(eval("(define n 0) (define s (stream-cons (begin (set! n (+ n 1)) n) stream-null)) \
+                         (stream-car s) (stream-car s) n"), "1");
+        assert_eq!(eval("(define seen (list)) \
+                         (define s (stream-map (lambda (x) (set! seen (cons x seen)) x) (list->stream (list 1 2 3)))) \
+                         (stream-car (stream-cdr s)) seen"), "(2)");
+    }
+
+    #[test]
+    fn test_infinite() {
+        assert_eq!(eval(&format!("{} (stream->list (stream-take 5 (integers-from 1)))", INTEGERS)), "(1 2 3 4 5)");
+        assert_eq!(eval(&format!("{} (stream->list (stream-take 3 (stream-map (lambda (x) (* x x)) (integers-from 1))))",
+            INTEGERS)), "(1 4 9)");
+        assert_eq!(eval(&format!("{} (stream->list (stream-take 3 (stream-filter odd? (integers-from 0))))", INTEGERS)),
+                   "(1 3 5)");
+        assert_eq!(eval(&format!("{} (stream-fold + 0 (stream-take 10 (integers-from 1)))", INTEGERS)), "55");
+        assert_eq!(eval("(stream->list (stream-take 5 (list->stream (list 1 2))))"), "(1 2)");
+    }
+
+    #[test]
+    fn test_long_streams() {
+        // far more elements than fit in memory at once, and far deeper than
+        // the Rust stack would allow if traversal recursed
+        let mut interpreter = Interpreter::new(4000);
+        interpreter.eval_string(INTEGERS).unwrap();
+        let res = interpreter.eval_string("(stream-fold + 0 (stream-take 20000 (integers-from 1)))").unwrap();
+        assert_eq!(interpreter.to_string(res), "200010000");
+        // start each traversal from a clean heap
+        interpreter.collect_garbage();
+        let res = interpreter.eval_string("(stream-car (stream-filter (lambda (x) (> x 20000)) (integers-from 0)))").unwrap();
+        assert_eq!(interpreter.to_string(res), "20001");
+        interpreter.collect_garbage();
+        let res = interpreter.eval_string("(length (stream->list (stream-take 1000 (integers-from 0))))").unwrap();
+        assert_eq!(interpreter.to_string(res), "1000");
+    }
+}
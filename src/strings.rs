@@ -0,0 +1,242 @@
+use crate::interpreter::Interpreter;
+use crate::native::{Arity, position, bound, range};
+use crate::sexpr::{SExpr, Object};
+
+// The R7RS string procedures. Strings are `Object::Str` cells in `Memory`
+// holding code points, so every index counts code points and `string-ref`
+// and `string-set!` take constant time.
+
+type Comparison = fn(&str, &str) -> bool;
+
+const COMPARISONS: &[(&str, &str, Comparison)] = &[
+    ("string=?", "string-ci=?", |a, b| a == b),
+    ("string<?", "string-ci<?", |a, b| a < b),
+    ("string>?", "string-ci>?", |a, b| a > b),
+    ("string<=?", "string-ci<=?", |a, b| a <= b),
+    ("string>=?", "string-ci>=?", |a, b| a >= b),
+];
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("string?", 1, |interp, args| {
+        Ok(interp.memory().str_ref(args[0]).is_ok())
+    });
+    interpreter.define_builtin("make-string", Arity::Between(1, 2), |interp, args| {
+        let k = bound(interp, args[0], usize::MAX)?;
+        let c = match args.get(1) {
+            Some(&c) => interp.from_sexpr(c)?,
+            None => ' ',
+        };
+        let mut s = Vec::new();
+        s.try_reserve_exact(k).or(Err("string too large"))?;
+        s.resize(k, c);
+        Ok(alloc(interp, s))
+    });
+    interpreter.define_builtin("string", Arity::AtLeast(0), |interp, args| {
+        let chars: Vec<char> = interp.args(args)?;
+        Ok(alloc(interp, chars))
+    });
+    interpreter.define_builtin("string-length", 1, |interp, args| {
+        Ok(string(interp, args[0])?.len() as i64)
+    });
+    interpreter.define_builtin("string-ref", 2, |interp, args| {
+        let s = string(interp, args[0])?;
+        let k = position(interp, args[1], s.len())?;
+        Ok(s[k])
+    });
+    interpreter.define_builtin("substring", Arity::Between(2, 3), |interp, args| {
+        let s = string(interp, args[0])?;
+        let (start, end) = range(interp, args, 1, s.len())?;
+        let s = s[start..end].to_vec();
+        Ok(alloc(interp, s))
+    });
+    interpreter.define_builtin("string-append", Arity::AtLeast(0), |interp, args| {
+        let mut res = Vec::new();
+        for &arg in args {
+            res.extend_from_slice(string(interp, arg)?);
+        }
+        Ok(alloc(interp, res))
+    });
+    for &(name, ci_name, cmp) in COMPARISONS {
+        interpreter.define_builtin(name, Arity::AtLeast(1), move |interp, args| {
+            let strings: Vec<String> = interp.args(args)?;
+            Ok(strings.windows(2).all(|w| cmp(&w[0], &w[1])))
+        });
+        interpreter.define_builtin(ci_name, Arity::AtLeast(1), move |interp, args| {
+            let strings: Vec<String> = interp.args(args)?;
+            let strings: Vec<String> = strings.iter().map(|s| foldcase(s)).collect();
+            Ok(strings.windows(2).all(|w| cmp(&w[0], &w[1])))
+        });
+    }
+    interpreter.define_builtin("string->list", Arity::Between(1, 3), |interp, args| {
+        let s = string(interp, args[0])?;
+        let (start, end) = range(interp, args, 1, s.len())?;
+        Ok(s[start..end].to_vec())
+    });
+    interpreter.define_builtin("list->string", 1, |interp, args| {
+        let chars: Vec<char> = interp.from_sexpr(args[0])?;
+        Ok(alloc(interp, chars))
+    });
+    interpreter.define_builtin("string-copy", Arity::Between(1, 3), |interp, args| {
+        let s = string(interp, args[0])?;
+        let (start, end) = range(interp, args, 1, s.len())?;
+        let s = s[start..end].to_vec();
+        Ok(alloc(interp, s))
+    });
+    interpreter.define_builtin("string-set!", 3, |interp, args| {
+        let k = position(interp, args[1], string(interp, args[0])?.len())?;
+        let c = interp.from_sexpr(args[2])?;
+        fill(interp, args[0], c, k, k + 1)
+    });
+    interpreter.define_builtin("string-fill!", Arity::Between(2, 4), |interp, args| {
+        let c = interp.from_sexpr(args[1])?;
+        let (start, end) = range(interp, args, 2, string(interp, args[0])?.len())?;
+        fill(interp, args[0], c, start, end)
+    });
+    interpreter.define_builtin("string-upcase", 1, |interp, args| {
+        let s: String = interp.from_sexpr(args[0])?;
+        Ok(s.to_uppercase())
+    });
+    interpreter.define_builtin("string-downcase", 1, |interp, args| {
+        let s: String = interp.from_sexpr(args[0])?;
+        Ok(s.to_lowercase())
+    });
+    interpreter.define_builtin("string-foldcase", 1, |interp, args| {
+        let s: String = interp.from_sexpr(args[0])?;
+        Ok(foldcase(&s))
+    });
+    interpreter.define_builtin("string-index", Arity::Between(2, 4), |interp, args| {
+        let s = string(interp, args[0])?.to_vec();
+        let (start, end) = range(interp, args, 2, s.len())?;
+        for (i, &c) in s.iter().enumerate().take(end).skip(start) {
+            let found = match args[1] {
+                SExpr::Char(x) => x == c,
+                pred => interp.apply(pred, &[SExpr::Char(c)])? != SExpr::Bool(false),
+            };
+            if found {
+                return Ok(SExpr::Int(i as i64));
+            }
+        }
+        Ok(SExpr::Bool(false))
+    });
+    interpreter.define_builtin("string-split", 2, |interp, args| {
+        let s: String = interp.from_sexpr(args[0])?;
+        let parts: Vec<String> = match args[1] {
+            SExpr::Char(c) => s.split(c).map(str::to_string).collect(),
+            delimiter => {
+                let delimiter: String = interp.from_sexpr(delimiter)?;
+                s.split(delimiter.as_str()).map(str::to_string).collect()
+            }
+        };
+        Ok(parts)
+    });
+    interpreter.define_builtin("string-join", Arity::Between(1, 2), |interp, args| {
+        let strings: Vec<String> = interp.from_sexpr(args[0])?;
+        let delimiter: String = match args.get(1) {
+            Some(&d) => interp.from_sexpr(d)?,
+            None => " ".to_string(),
+        };
+        Ok(strings.join(&delimiter))
+    });
+}
+
+fn string(interp: &Interpreter, e: SExpr) -> Result<&[char], &'static str> {
+    interp.memory().str_ref(e)
+}
+
+fn alloc(interp: &mut Interpreter, chars: Vec<char>) -> SExpr {
+    interp.memory_mut().alloc(Object::Str(chars))
+}
+
+// Full case folding, which unlike lowercasing makes "straße" and "STRASSE"
+// equal. Going through uppercase first folds the few characters, like final
+// sigma, whose lowercase is not their folded form.
+fn foldcase(s: &str) -> String {
+    s.to_uppercase().to_lowercase()
+}
+
+fn fill(interp: &mut Interpreter, e: SExpr, c: char, start: usize, end: usize) -> Result<SExpr, &'static str> {
+    let s = interp.memory_mut().str_mut(e)?;
+    s[start..end].fill(c);
+    Ok(SExpr::Nil)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::{Interpreter, eval_to_string as eval};
+    use crate::sexpr::SExpr;
+
+    #[test]
+    fn test_access() {
+        assert_eq!(eval("(string-length \"λx.x\")"), "4");
+        assert_eq!(eval("(string-ref \"aλb\" 1)"), "#\\λ");
+        assert_eq!(eval("(string-ref \"abc\" 3)"), "index out of range");
+        assert_eq!(eval("(substring \"hello\" 1 3)"), "\"el\"");
+        assert_eq!(eval("(substring \"hello\" 2)"), "\"llo\"");
+        assert_eq!(eval("(substring \"hello\" 3 2)"), "index out of range");
+        assert_eq!(eval("(string-append \"a\" \"bc\" \"\")"), "\"abc\"");
+        assert_eq!(eval("(string-length 1)"), "type error: expected string");
+        assert_eq!(eval("(string? \"a\")"), "true");
+        assert_eq!(eval("(string? (quote a))"), "false");
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(eval("(string=? \"abc\" \"abc\" \"abc\")"), "true");
+        assert_eq!(eval("(string<? \"abc\" \"abd\")"), "true");
+        assert_eq!(eval("(string>? \"abc\" \"abd\")"), "false");
+        assert_eq!(eval("(string<=? \"a\" \"a\" \"b\")"), "true");
+        assert_eq!(eval("(string-ci=? \"AbC\" \"aBc\")"), "true");
+        assert_eq!(eval("(string-ci=? \"Straße\" \"STRASSE\")"), "true");
+        assert_eq!(eval("(string-ci=? \"οδοσ\" \"οδος\")"), "true");
+        assert_eq!(eval("(string-ci<? \"a\" \"B\")"), "true");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(eval("(string->list \"aλ\")"), "(#\\a #\\λ)");
+        assert_eq!(eval("(list->string (string->list \"hello\" 1 4))"), "\"ell\"");
+        assert_eq!(eval("(string-upcase \"straße\")"), "\"STRASSE\"");
+        assert_eq!(eval("(string-downcase \"ΛX\")"), "\"λx\"");
+        assert_eq!(eval("(string-foldcase \"Straße\")"), "\"strasse\"");
+        assert_eq!(eval("(make-string 3 #\\λ)"), "\"λλλ\"");
+    }
+
+    #[test]
+    fn test_long_search() {
+        // the predicate allocates, far more in total than the heap holds
+        let mut interpreter = Interpreter::new(1000);
+        let res = interpreter.eval_string("(define s (make-string 3000 #\\a)) (string-set! s 2999 #\\b) \
+            (string-index s (lambda (c) (length (list 1 2 3 4 5 6 7 8 9 10)) (char=? c #\\b)))");
+        assert_eq!(res, Ok(SExpr::Int(2999)));
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(eval("(list->string (list #\\\" #\\a))"), r#""\"a""#);
+        assert_eq!(eval(r#"(string->list "\"\\\n\t\x3bb;")"#), r#"(#\" #\\ #\newline #\tab #\λ)"#);
+        assert_eq!(eval(r#"(string-length "a\"b")"#), "3");
+        // what is printed reads back as the same string
+        let mut interpreter = Interpreter::new(2000);
+        let s = interpreter.eval_string(r#"(define s (list->string (list #\" #\\ #\newline #\x7))) s"#).unwrap();
+        let printed = interpreter.to_string(s);
+        let res = interpreter.eval_string(&format!("(equal? {} s)", printed));
+        assert_eq!(res, Ok(SExpr::Bool(true)));
+    }
+
+    #[test]
+    fn test_mutation() {
+        assert_eq!(eval("(define s (string-copy \"aλc\")) (string-set! s 1 #\\b) s"), "\"abc\"");
+        assert_eq!(eval("(define s (make-string 4)) (string-fill! s #\\x 1 3) s"), "\" xx \"");
+        assert_eq!(eval("(define s \"abc\") (define t (string-copy s)) (string-fill! t #\\z) s"), "\"abc\"");
+    }
+
+    #[test]
+    fn test_split_join() {
+        assert_eq!(eval("(string-split \"a,b,,c\" \",\")"), "(\"a\" \"b\" \"\" \"c\")");
+        assert_eq!(eval("(string-join (list \"a\" \"b\" \"c\") \", \")"), "\"a, b, c\"");
+        assert_eq!(eval("(string-join (string-split \"x y\" \" \"))"), "\"x y\"");
+        assert_eq!(eval("(string-index \"hello\" #\\l)"), "2");
+        assert_eq!(eval("(string-index \"hello\" (lambda (c) (string=? (string c) \"o\")))"), "4");
+        assert_eq!(eval("(string-index \"hello\" #\\z)"), "false");
+    }
+}
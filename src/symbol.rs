@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+/// An interned symbol. Symbols are only meaningful together with the
+/// `Memory` whose symbol table produced them.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Copy, Clone)]
+pub struct Symbol(usize);
+
+// Symbols the interpreter dispatches on are interned up front so they can be
+// used as constants in patterns.
+macro_rules! well_known_symbols {
+    ( $( $name:ident $s:expr ),* $(,)? ) => {
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        enum WellKnown { $( $name ),* }
+
+        $( pub const $name: Symbol = Symbol(WellKnown::$name as usize); )*
+
+        const WELL_KNOWN: &[&str] = &[ $( $s ),* ];
+    };
+}
+
+well_known_symbols! {
+    QUOTE "quote",
+    SET "set!",
+    DEFINE "define",
+    IF "if",
+    BEGIN "begin",
+    LAMBDA "lambda",
+    COND "cond",
+    LET "let",
+    ELSE "else",
+    DEFINE_RECORD_TYPE "define-record-type",
+    THE_ENVIRONMENT "the-environment",
+    DELAY "delay",
+    DELAY_FORCE "delay-force",
+    CONS_STREAM "cons-stream",
+    STREAM_CONS "stream-cons",
+    DEFINE_STREAM "define-stream",
+}
+
+#[derive(PartialEq, Debug)]
+pub struct SymbolTable {
+    names: Vec<String>,
+    ids: HashMap<String, Symbol>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        SymbolTable::new()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        let mut table = SymbolTable { names: Vec::new(), ids: HashMap::new() };
+        for name in WELL_KNOWN {
+            table.intern(name);
+        }
+        table
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&sym) = self.ids.get(name) {
+            return sym;
+        }
+        let sym = Symbol(self.names.len());
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), sym);
+        sym
+    }
+
+    /// Finds `name` without interning it.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, sym: Symbol) -> &str {
+        &self.names[sym.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut table = SymbolTable::new();
+        let a = table.intern("abc");
+        assert_eq!(table.intern("abc"), a);
+        assert_ne!(table.intern("abd"), a);
+        assert_eq!(table.name(a), "abc");
+        assert_eq!(table.get("abc"), Some(a));
+        assert_eq!(table.get("abe"), None);
+    }
+
+    #[test]
+    fn test_well_known() {
+        let mut table = SymbolTable::new();
+        assert_eq!(table.intern("quote"), QUOTE);
+        assert_eq!(table.intern("set!"), SET);
+        assert_eq!(table.intern("else"), ELSE);
+        assert_eq!(table.name(LAMBDA), "lambda");
+    }
+}
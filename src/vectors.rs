@@ -0,0 +1,153 @@
+use crate::interpreter::Interpreter;
+use crate::native::{Arity, position, bound, range};
+use crate::sexpr::{SExpr, Object};
+
+// The R7RS vector procedures. Vectors are `Object::Vector` cells holding
+// their elements contiguously, so indexing is O(1).
+
+pub fn define_procedures(interpreter: &mut Interpreter) {
+    interpreter.define_builtin("vector?", 1, |interp, args| {
+        Ok(interp.memory().vector_ref(args[0]).is_ok())
+    });
+    interpreter.define_builtin("make-vector", Arity::Between(1, 2), |interp, args| {
+        let k = bound(interp, args[0], usize::MAX)?;
+        let fill = args.get(1).copied().unwrap_or(SExpr::Nil);
+        let mut elements = Vec::new();
+        elements.try_reserve_exact(k).or(Err("vector too large"))?;
+        elements.resize(k, fill);
+        Ok(alloc(interp, elements))
+    });
+    interpreter.define_builtin("vector", Arity::AtLeast(0), |interp, args| {
+        Ok(alloc(interp, args.to_vec()))
+    });
+    interpreter.define_builtin("vector-length", 1, |interp, args| {
+        Ok(vector(interp, args[0])?.len() as i64)
+    });
+    interpreter.define_builtin("vector-ref", 2, |interp, args| {
+        let v = vector(interp, args[0])?;
+        let k = position(interp, args[1], v.len())?;
+        Ok(v[k])
+    });
+    interpreter.define_builtin("vector-set!", 3, |interp, args| {
+        let k = position(interp, args[1], vector(interp, args[0])?.len())?;
+        vector_mut(interp, args[0])?[k] = args[2];
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("vector->list", Arity::Between(1, 3), |interp, args| {
+        let v = vector(interp, args[0])?;
+        let (start, end) = range(interp, args, 1, v.len())?;
+        let elements = v[start..end].to_vec();
+        Ok(interp.memory_mut().list_from_vec(elements))
+    });
+    interpreter.define_builtin("list->vector", 1, |interp, args| {
+        let elements = interp.memory().vec_from_list(args[0]).or(Err("type error: expected list"))?;
+        Ok(alloc(interp, elements))
+    });
+    interpreter.define_builtin("vector-fill!", Arity::Between(2, 4), |interp, args| {
+        let (start, end) = range(interp, args, 2, vector(interp, args[0])?.len())?;
+        for e in &mut vector_mut(interp, args[0])?[start..end] {
+            *e = args[1];
+        }
+        Ok(SExpr::Nil)
+    });
+    interpreter.define_builtin("vector-copy", Arity::Between(1, 3), |interp, args| {
+        let v = vector(interp, args[0])?;
+        let (start, end) = range(interp, args, 1, v.len())?;
+        let elements = v[start..end].to_vec();
+        Ok(alloc(interp, elements))
+    });
+    interpreter.define_builtin("vector-map", Arity::AtLeast(2), |interp, args| {
+        let columns = columns(interp, &args[1..])?;
+        let mut res = Vec::with_capacity(columns.len());
+        for column in columns {
+            // a collection during a later call must not free it
+            let value = interp.apply(args[0], &column)?;
+            interp.root(value);
+            res.push(value);
+        }
+        Ok(alloc(interp, res))
+    });
+    interpreter.define_builtin("vector-for-each", Arity::AtLeast(2), |interp, args| {
+        for column in columns(interp, &args[1..])? {
+            interp.apply(args[0], &column)?;
+        }
+        Ok(SExpr::Nil)
+    });
+}
+
+fn alloc(interp: &mut Interpreter, elements: Vec<SExpr>) -> SExpr {
+    interp.memory_mut().alloc(Object::Vector(elements))
+}
+
+fn vector(interp: &Interpreter, e: SExpr) -> Result<&Vec<SExpr>, &'static str> {
+    interp.memory().vector_ref(e).or(Err("type error: expected vector"))
+}
+
+fn vector_mut(interp: &mut Interpreter, e: SExpr) -> Result<&mut Vec<SExpr>, &'static str> {
+    interp.memory_mut().vector_mut(e).or(Err("type error: expected vector"))
+}
+
+// The i-th elements of every vector, up to the length of the shortest one.
+fn columns(interp: &Interpreter, vectors: &[SExpr]) -> Result<Vec<Vec<SExpr>>, &'static str> {
+    let vectors = vectors.iter().map(|&v| vector(interp, v)).collect::<Result<Vec<_>, _>>()?;
+    let len = vectors.iter().map(|v| v.len()).min().unwrap_or(0);
+    Ok((0..len).map(|i| vectors.iter().map(|v| v[i]).collect()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::{Interpreter, eval_to_string as eval};
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(eval("#(1 \"a\" #\\b (c))"), "#(1 \"a\" #\\b (c))");
+        assert_eq!(eval("#()"), "#()");
+        assert_eq!(eval("(vector 1 (+ 1 1))"), "#(1 2)");
+        assert_eq!(eval("(make-vector 3 0)"), "#(0 0 0)");
+        assert_eq!(eval("(vector? #(1))"), "true");
+        assert_eq!(eval("(vector? (list 1))"), "false");
+    }
+
+    #[test]
+    fn test_access() {
+        assert_eq!(eval("(vector-ref #(1 2 3) 2)"), "3");
+        assert_eq!(eval("(vector-ref #(1 2 3) 3)"), "index out of range");
+        assert_eq!(eval("(vector-ref #(1 2 3) -1)"), "index out of range");
+        assert_eq!(eval("(vector-ref (list 1) 0)"), "type error: expected vector");
+        assert_eq!(eval("(vector-length (make-vector 5))"), "5");
+        assert_eq!(eval("(make-vector 1000000000000000000)"), "vector too large");
+        assert_eq!(eval("(define v (vector 1 2 3)) (vector-set! v 0 #t) v"), "#(true 2 3)");
+        assert_eq!(eval("(define v (make-vector 4 0)) (vector-fill! v 1 1 3) v"), "#(0 1 1 0)");
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(eval("(vector->list #(1 2 3))"), "(1 2 3)");
+        assert_eq!(eval("(vector->list #(1 2 3) 1)"), "(2 3)");
+        assert_eq!(eval("(list->vector (list 1 2))"), "#(1 2)");
+        assert_eq!(eval("(define v #(1 2 3)) (define w (vector-copy v 1)) (vector-set! w 0 9) (list v w)"),
+            "(#(1 2 3) #(9 3))");
+    }
+
+    #[test]
+    fn test_higher_order() {
+        assert_eq!(eval("(vector-map (lambda (x) (* x x)) #(1 2 3))"), "#(1 4 9)");
+        assert_eq!(eval("(vector-map + #(1 2 3) #(10 20))"), "#(11 22)");
+        assert_eq!(eval("(define sum 0) (vector-for-each (lambda (x) (set! sum (+ sum x))) #(1 2 3)) sum"), "6");
+    }
+
+    #[test]
+    fn test_long_vectors() {
+        // each call allocates, so the heap fills up many times over while
+        // the results built so far must survive
+        let mut interpreter = Interpreter::new(4000);
+        interpreter.eval_string("(define v (list->vector (iota 300))) \
+            (define (waste x) (length (list 1 2 3 4 5 6 7 8 9 10)) x)").unwrap();
+        let res = interpreter.eval_string("(define w (vector-map (lambda (x) (list (waste x))) v)) \
+            (apply + (map car (vector->list w)))").unwrap();
+        assert_eq!(interpreter.to_string(res), "44850");
+        interpreter.collect_garbage();
+        let res = interpreter.eval_string("(define n 0) (vector-for-each (lambda (x) (set! n (+ n (waste 1)))) v) n").unwrap();
+        assert_eq!(interpreter.to_string(res), "300");
+    }
+}